@@ -3,26 +3,82 @@
 #![allow(clippy::cast_possible_truncation)]
 
 use std::future::Future;
+use std::num::NonZeroUsize;
+use std::ops::{Range, RangeInclusive};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
 #[cfg(feature = "__async")]
-use tokio::io::AsyncReadExt;
+use tokio::io::AsyncRead;
 
+use crate::bbox::{overlaps_any, tile_id_ranges};
 use crate::cache::DirCacheResult;
 #[cfg(feature = "__async")]
 use crate::cache::{DirectoryCache, NoCache};
+use crate::codec::decompress;
 use crate::directory::{DirEntry, Directory};
-use crate::error::{PmtError, PmtResult};
+use crate::entry_cache::EntryCache;
+use crate::error::{BackendError, PmtError, PmtResult};
 use crate::header::{HEADER_SIZE, MAX_INITIAL_BYTES};
-use crate::tile::tile_id;
+use crate::stats::{ArchiveStats, IoCounters, IoStats, ReadPurpose};
+use crate::tile::{tile_extent, tile_id, TileCoord, TileId, MAX_ZOOM};
 use crate::PmtError::UnsupportedCompression;
-use crate::{Compression, Header};
+use crate::{BoundingBox, Compression, Header};
 
+/// Reads tiles, metadata and directories of a `PMTiles` archive from an [`AsyncBackend`].
+///
+/// With the `metrics` feature enabled, the reader records these metrics:
+/// - `pmtiles_backend_requests_total`, `pmtiles_backend_errors_total` and `pmtiles_backend_bytes_read`
+///   counters, and a `pmtiles_backend_read_duration_seconds` histogram for requests made to the backend
+/// - a `pmtiles_tile_fetch_duration_seconds` histogram for [`get_tile`](Self::get_tile) calls
+/// - `pmtiles_directory_cache_hits_total` and `pmtiles_directory_cache_misses_total` counters,
+///   from which the cache hit ratio can be derived
+///
+/// Cloning a reader is cheap: clones share the backend, cache, parsed root directory and
+/// I/O statistics, so they can be handed to request handlers or spawned tasks directly.
+/// Only the [timeout](Self::with_timeout), [bounds check](Self::with_bounds_check) and
+/// [neighbor prefetch](Self::with_neighbor_prefetch) settings are kept per handle.
 pub struct AsyncPmTilesReader<B, C = NoCache> {
-    backend: B,
-    cache: C,
+    backend: Arc<B>,
+    cache: Arc<C>,
     header: Header,
-    root_directory: Directory,
+    root_directory: Arc<Directory>,
+    prefetched: Bytes,
+    io: Arc<IoCounters>,
+    timeout: Option<Duration>,
+    bounds_check: bool,
+    neighbor_prefetch: usize,
+    entries: Option<Arc<Mutex<EntryCache>>>,
+}
+
+impl<B, C> Clone for AsyncPmTilesReader<B, C> {
+    fn clone(&self) -> Self {
+        Self {
+            backend: Arc::clone(&self.backend),
+            cache: Arc::clone(&self.cache),
+            header: self.header.clone(),
+            root_directory: Arc::clone(&self.root_directory),
+            prefetched: self.prefetched.clone(),
+            io: Arc::clone(&self.io),
+            timeout: self.timeout,
+            bounds_check: self.bounds_check,
+            neighbor_prefetch: self.neighbor_prefetch,
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<B, C> AsyncPmTilesReader<B, C> {
+    /// The tile IDs in the entry cache, least recently used first.
+    pub(crate) fn hot_tile_ids(&self) -> Vec<u64> {
+        self.entries.as_ref().map_or_else(Vec::new, |entries| {
+            entries
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .tile_ids()
+        })
+    }
 }
 
 impl<B: AsyncBackend + Sync + Send> AsyncPmTilesReader<B, NoCache> {
@@ -39,40 +95,323 @@ impl<B: AsyncBackend + Sync + Send, C: DirectoryCache + Sync + Send> AsyncPmTile
     ///
     /// Note: Prefer using `new_with_*` methods.
     pub async fn try_from_cached_source(backend: B, cache: C) -> PmtResult<Self> {
+        Self::try_from_cached_source_with_prefetch(backend, cache, MAX_INITIAL_BYTES).await
+    }
+
+    /// Creates a new cached reader like [`Self::try_from_cached_source`], but reads up to
+    /// `prefetch_length` bytes from the start of the archive in the initial request.
+    ///
+    /// The prefetched bytes are kept for the lifetime of the reader and used to serve metadata,
+    /// leaf directory and tile reads that fall within them, avoiding further backend requests.
+    /// Values smaller than the 16,384 bytes needed for the header and root directory are ignored.
+    pub async fn try_from_cached_source_with_prefetch(
+        backend: B,
+        cache: C,
+        prefetch_length: usize,
+    ) -> PmtResult<Self> {
         // Read the first 127 and up to 16,384 bytes to ensure we can initialize the header and root directory.
-        let mut initial_bytes = backend.read(0, MAX_INITIAL_BYTES).await?;
-        if initial_bytes.len() < HEADER_SIZE {
+        let length = prefetch_length.max(MAX_INITIAL_BYTES);
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let start = Instant::now();
+        let prefetched = backend
+            .read(0, length)
+            .await
+            .map_err(|e| backend_error(&backend, e, 0, length));
+        let io = IoCounters::default();
+        io.record(ReadPurpose::Header, &prefetched);
+        #[cfg(feature = "metrics")]
+        record_backend_read(&prefetched, start.elapsed());
+        let prefetched = prefetched?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            length,
+            received = prefetched.len(),
+            duration = ?start.elapsed(),
+            "read archive prefix"
+        );
+        if prefetched.len() < HEADER_SIZE {
             return Err(PmtError::InvalidHeader);
         }
 
-        let header = Header::try_from_bytes(initial_bytes.split_to(HEADER_SIZE))?;
+        let header = Header::try_from_bytes(prefetched.slice(..HEADER_SIZE))?;
+        #[cfg(feature = "tracing")]
+        if header.check_spec_version().is_err() {
+            tracing::warn!(
+                version = header.spec_version(),
+                "archive declares an unsupported spec version, reading it as v3"
+            );
+        }
+        if let Some(size) = backend.size() {
+            let expected = header.layout().end();
+            if expected > size {
+                return Err(PmtError::ArchiveTruncated(expected, size));
+            }
+        }
 
-        let directory_bytes = initial_bytes
-            .split_off((header.root_offset as usize) - HEADER_SIZE)
-            .split_to(header.root_length as _);
+        let root_start = header.root_offset as usize;
+        let root_end = root_start + header.root_length as usize;
+        if root_start < HEADER_SIZE || root_end > prefetched.len() {
+            return Err(PmtError::InvalidHeader);
+        }
+        let directory_bytes = prefetched.slice(root_start..root_end);
 
         let root_directory =
             Self::read_compressed_directory(header.internal_compression, directory_bytes).await?;
 
         Ok(Self {
-            backend,
-            cache,
+            backend: Arc::new(backend),
+            cache: Arc::new(cache),
             header,
-            root_directory,
+            root_directory: Arc::new(root_directory),
+            prefetched,
+            io: Arc::new(io),
+            timeout: None,
+            bounds_check: false,
+            neighbor_prefetch: 0,
+            entries: None,
         })
     }
 
     /// Fetches tile bytes from the archive.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
     pub async fn get_tile(&self, z: u8, x: u64, y: u64) -> PmtResult<Option<Bytes>> {
-        let tile_id = tile_id(z, x, y);
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        self.get_tile_until(z, x, y, deadline).await
+    }
+
+    /// Fetches tile bytes from the archive, failing with [`PmtError::Timeout`] if the tile
+    /// could not be read by `deadline`. The reader's own timeout is not applied.
+    pub async fn get_tile_with_deadline(
+        &self,
+        z: u8,
+        x: u64,
+        y: u64,
+        deadline: Instant,
+    ) -> PmtResult<Option<Bytes>> {
+        self.get_tile_until(z, x, y, Some(deadline)).await
+    }
+
+    /// Fetches tile bytes together with the archive's tile compression and the tile's
+    /// content type, i.e. everything needed to serve the tile as-is.
+    pub async fn get_tile_info(
+        &self,
+        tile_id: TileId,
+    ) -> PmtResult<Option<(Bytes, Compression, &'static str)>> {
+        let coord = TileCoord::from(tile_id);
+        let Some(tile) = self.get_tile(coord.z(), coord.x(), coord.y()).await? else {
+            return Ok(None);
+        };
+        Ok(Some((
+            tile,
+            self.header.tile_compression,
+            self.header.tile_type.content_type(),
+        )))
+    }
+
+    async fn get_tile_until(
+        &self,
+        z: u8,
+        x: u64,
+        y: u64,
+        deadline: Option<Instant>,
+    ) -> PmtResult<Option<Bytes>> {
+        if self.bounds_check
+            && !TileCoord::new(z, x, y).is_ok_and(|coord| self.header.contains_tile(coord))
+        {
+            return Ok(None);
+        }
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let tile = until(deadline, self.fetch_tile(tile_id(z, x, y))).await;
+        #[cfg(feature = "metrics")]
+        metrics::histogram!("pmtiles_tile_fetch_duration_seconds").record(start.elapsed());
+        if self.neighbor_prefetch > 0 && matches!(tile, Ok(Some(_))) {
+            let budget = self.neighbor_prefetch;
+            #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+            let prefetched = until(deadline, self.prefetch_neighbors(z, x, y, budget)).await;
+            #[cfg(feature = "tracing")]
+            if let Err(e) = prefetched {
+                tracing::debug!(error = %e, "prefetching neighbors failed");
+            }
+        }
+        tile
+    }
+
+    async fn fetch_tile(&self, tile_id: u64) -> PmtResult<Option<Bytes>> {
         let Some(entry) = self.find_tile_entry(tile_id).await? else {
             return Ok(None);
         };
 
+        Ok(Some(self.read_tile_data(&entry).await?))
+    }
+
+    /// Streams a tile's data instead of buffering it, e.g. to pass very large tiles on to a client.
+    ///
+    /// Backends that support it, such as [`HttpBackend`](crate::HttpBackend), pass the bytes on as
+    /// they arrive, others read the whole tile first, see [`AsyncBackend::read_stream`].
+    /// With `decompress_tile`, the data is decompressed with the archive's tile compression while it is read.
+    /// The reader's timeout only applies to locating the tile and starting the stream.
+    pub async fn get_tile_stream(
+        &self,
+        tile_id: TileId,
+        decompress_tile: bool,
+    ) -> PmtResult<Option<TileStream>> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let Some(entry) = until(deadline, self.find_tile_entry(tile_id.value())).await? else {
+            return Ok(None);
+        };
+        let offset = self
+            .header
+            .data_offset
+            .checked_add(entry.offset)
+            .and_then(|offset| usize::try_from(offset).ok())
+            .ok_or(PmtError::InvalidEntry)?;
+        let length = entry.length as usize;
+
+        let end = offset.checked_add(length).ok_or(PmtError::InvalidEntry)?;
+        let stream: TileStream = if end <= self.prefetched.len() {
+            Box::new(std::io::Cursor::new(self.prefetched.slice(offset..end)))
+        } else {
+            let result = until(deadline, self.backend.read_stream(offset, length))
+                .await
+                .map_err(|e| backend_error(&*self.backend, e, offset, length));
+            self.io.record_stream(ReadPurpose::Tile, length, &result);
+            result?
+        };
+        if !decompress_tile {
+            return Ok(Some(stream));
+        }
+        Ok(Some(match self.header.tile_compression {
+            Compression::None => stream,
+            Compression::Gzip => Box::new(async_compression::tokio::bufread::GzipDecoder::new(
+                tokio::io::BufReader::new(stream),
+            )),
+            v => Err(UnsupportedCompression(v))?,
+        }))
+    }
+
+    /// Reads the tile data a tile entry points to.
+    pub(crate) async fn read_tile_data(&self, entry: &DirEntry) -> PmtResult<Bytes> {
         let offset = (self.header.data_offset + entry.offset) as _;
         let length = entry.length as _;
 
-        Ok(Some(self.backend.read_exact(offset, length).await?))
+        self.read_exact(offset, length, ReadPurpose::Tile).await
+    }
+
+    /// Limits each [`get_tile`](Self::get_tile) and [`get_metadata`](Self::get_metadata) call to `timeout`.
+    /// Calls taking longer are cancelled, including any pending backend reads,
+    /// and fail with [`PmtError::Timeout`].
+    ///
+    /// The Tokio runtime must have its time driver enabled.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Makes [`get_tile`](Self::get_tile) return `Ok(None)` for tiles outside the header's zoom
+    /// range and bounds without reading any directory, see [`Header::contains_tile`].
+    /// Only use this with archives whose header describes their contents accurately.
+    #[must_use]
+    pub fn with_bounds_check(mut self) -> Self {
+        self.bounds_check = true;
+        self
+    }
+
+    /// Makes [`get_tile`](Self::get_tile) look up at most `budget` neighbors of each tile it finds,
+    /// see [`prefetch_neighbors`](Self::prefetch_neighbors), so that panning or zooming in finds
+    /// their directories cached. A budget of 0 turns this off again.
+    ///
+    /// The lookups happen before `get_tile` returns and are bounded by its timeout, so they add
+    /// latency whenever they miss the cache. Failed lookups are ignored.
+    /// This only does useful work with a real [`DirectoryCache`], not with [`NoCache`].
+    #[must_use]
+    pub fn with_neighbor_prefetch(mut self, budget: usize) -> Self {
+        self.neighbor_prefetch = budget;
+        self
+    }
+
+    /// Remembers the directory entries of up to `capacity` recently requested tiles, including
+    /// tiles found to be missing, so requests for hot tiles skip the directory search and cache.
+    /// The entries are shared with clones made afterwards.
+    #[must_use]
+    pub fn with_entry_cache(mut self, capacity: NonZeroUsize) -> Self {
+        self.entries = Some(Arc::new(Mutex::new(EntryCache::new(capacity))));
+        self
+    }
+
+    /// Checks that the archive is still reachable and unchanged since the reader was opened,
+    /// by reading the header again and comparing it to the one read at open time.
+    ///
+    /// The check is subject to the reader's [timeout](Self::with_timeout).
+    pub async fn health_check(&self) -> HealthStatus {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let read = async {
+            let result = self
+                .backend
+                .read_exact(0, HEADER_SIZE)
+                .await
+                .map_err(|e| backend_error(&*self.backend, e, 0, HEADER_SIZE));
+            self.io.record(ReadPurpose::Header, &result);
+            result
+        };
+
+        match until(deadline, read).await {
+            Ok(header) if header == self.prefetched.slice(..HEADER_SIZE) => HealthStatus::Ok,
+            Err(e) if !is_archive_changed(&e) => HealthStatus::Unreachable(e),
+            _ => {
+                self.invalidate_cache().await;
+                HealthStatus::Changed
+            }
+        }
+    }
+
+    /// Removes all directories from the cache, e.g. before sharing it with a reader for a
+    /// replaced archive. Happens automatically once the backend reports that the archive
+    /// has changed, see [`PmtError::ArchiveChanged`] and [`Self::health_check`].
+    pub async fn invalidate_cache(&self) {
+        if let Some(entries) = &self.entries {
+            entries
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clear();
+        }
+        self.cache.invalidate_all().await;
+    }
+
+    /// Looks up the tiles in `previous`'s [entry cache](Self::with_entry_cache) in this reader,
+    /// filling its directory and entry caches. Returns the number of tiles looked up.
+    ///
+    /// Meant for archives that are republished periodically: once [`Self::health_check`]
+    /// reports [`HealthStatus::Changed`], open a new reader, warm it from the old one, and then
+    /// swap it in, so requests for hot tiles don't all miss the cache at once.
+    /// [`RefreshingReader`](crate::RefreshingReader) does this on an interval.
+    pub async fn warm_from<B2, C2>(
+        &self,
+        previous: &AsyncPmTilesReader<B2, C2>,
+    ) -> PmtResult<usize> {
+        self.warm_tiles(&previous.hot_tile_ids()).await
+    }
+
+    /// Looks up `tile_ids`, filling the directory and entry caches.
+    pub(crate) async fn warm_tiles(&self, tile_ids: &[u64]) -> PmtResult<usize> {
+        for &tile_id in tile_ids {
+            self.find_tile_entry(tile_id).await?;
+        }
+        Ok(tile_ids.len())
+    }
+
+    /// Counts of the backend requests this reader has made so far, by purpose.
+    pub fn io_stats(&self) -> IoStats {
+        self.io.snapshot()
+    }
+
+    /// Tells the backend how upcoming reads are going to be made, see [`AsyncBackend::hint`].
+    pub fn hint(&self, pattern: AccessPattern) {
+        self.backend.hint(pattern);
     }
 
     /// Access header information.
@@ -80,19 +419,53 @@ impl<B: AsyncBackend + Sync + Send, C: DirectoryCache + Sync + Send> AsyncPmTile
         &self.header
     }
 
+    /// The root directory, as read when the reader was opened.
+    pub fn root_directory(&self) -> &Directory {
+        &self.root_directory
+    }
+
+    /// Number of leaf directories referenced by the root directory.
+    /// Leaves may reference further leaves, so the archive can hold more in total.
+    pub fn leaf_count(&self) -> usize {
+        self.root_directory.iter().filter(DirEntry::is_leaf).count()
+    }
+
+    /// Reads the leaf directory a leaf pointer of the root or another leaf directory points to,
+    /// bypassing the directory cache.
+    ///
+    /// Fails with [`PmtError::InvalidEntry`] if `entry` points to tile data instead.
+    pub async fn read_leaf_directory(&self, entry: &DirEntry) -> PmtResult<Directory> {
+        if !entry.is_leaf() {
+            return Err(PmtError::InvalidEntry);
+        }
+        let offset = (self.header.leaf_offset + entry.offset) as _;
+        self.read_directory(offset, entry.length as _).await
+    }
+
     /// Gets metadata from the archive.
     ///
     /// Note: by spec, this should be valid JSON. This method currently returns a [String].
     /// This may change in the future.
     pub async fn get_metadata(&self) -> PmtResult<String> {
+        let (metadata, compression) = self.get_metadata_raw().await?;
+        let decompressed_metadata = decompress(compression, metadata).await?;
+
+        Ok(String::from_utf8(decompressed_metadata.to_vec())?)
+    }
+
+    /// Gets the metadata bytes as stored in the archive, along with the compression applied to them,
+    /// e.g. to serve them with a matching `Content-Encoding` without decompressing.
+    pub async fn get_metadata_raw(&self) -> PmtResult<(Bytes, Compression)> {
         let offset = self.header.metadata_offset as _;
         let length = self.header.metadata_length as _;
-        let metadata = self.backend.read_exact(offset, length).await?;
-
-        let decompressed_metadata =
-            Self::decompress(self.header.internal_compression, metadata).await?;
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let metadata = until(
+            deadline,
+            self.read_exact(offset, length, ReadPurpose::Metadata),
+        )
+        .await?;
 
-        Ok(String::from_utf8(decompressed_metadata.to_vec())?)
+        Ok((metadata, self.header.internal_compression))
     }
 
     #[cfg(feature = "tilejson")]
@@ -137,16 +510,266 @@ impl<B: AsyncBackend + Sync + Send, C: DirectoryCache + Sync + Send> AsyncPmTile
         Ok(tj)
     }
 
-    /// Recursively locates a tile in the archive.
+    /// Parses the `vector_layers` entry of the metadata, describing the layers of the vector tiles
+    /// and the types of their fields. Returns an empty list if the metadata has no such entry.
+    #[cfg(feature = "tilejson")]
+    pub async fn vector_layers(&self) -> PmtResult<Vec<tilejson::VectorLayer>> {
+        parse_vector_layers(&self.get_metadata().await?)
+    }
+
+    /// Fetches the leaf directories needed to serve tiles within `bbox` (or the whole archive if `None`)
+    /// at the given zoom levels, and stores them in the directory cache.
+    ///
+    /// Leaf directories that are adjacent in the archive are fetched with a single backend read.
+    /// Directories already in the cache are not inserted again, but are still read, as the leaf
+    /// directories below them may not be cached. Returns the number of directories inserted.
+    /// This only does useful work with a real [`DirectoryCache`], not with [`NoCache`].
+    pub async fn warm_cache(
+        &self,
+        bbox: Option<BoundingBox>,
+        zoom_range: RangeInclusive<u8>,
+    ) -> PmtResult<usize> {
+        let ranges = tile_id_ranges(bbox.as_ref(), zoom_range);
+        let mut pending = relevant_leaves(&self.root_directory, u64::MAX, &ranges);
+        let mut inserted = 0;
+
+        for _ in 0..MAX_LEAF_DEPTH {
+            if pending.is_empty() {
+                break;
+            }
+            let mut leaves = Vec::with_capacity(pending.len());
+            for (entry, end) in pending {
+                let offset = (self.header.leaf_offset + entry.offset) as usize;
+                let cached = !matches!(
+                    self.cache.get_dir_entry(offset, entry.tile_id).await,
+                    DirCacheResult::NotCached
+                );
+                leaves.push((entry, end, cached));
+            }
+            leaves.sort_by_key(|(entry, _, _)| entry.offset);
+            leaves.dedup_by_key(|(entry, _, _)| entry.offset);
+
+            let mut next = Vec::new();
+            for group in
+                leaves.chunk_by(|(a, _, _), (b, _, _)| a.offset + u64::from(a.length) == b.offset)
+            {
+                let group_offset = group[0].0.offset;
+                let group_length: u64 = group.iter().map(|(e, _, _)| u64::from(e.length)).sum();
+                let data = self
+                    .read_exact(
+                        (self.header.leaf_offset + group_offset) as usize,
+                        group_length as usize,
+                        ReadPurpose::Directory,
+                    )
+                    .await?;
+
+                for (entry, end, cached) in group {
+                    let start = (entry.offset - group_offset) as usize;
+                    let bytes = data.slice(start..start + entry.length as usize);
+                    let dir =
+                        Self::read_compressed_directory(self.header.internal_compression, bytes)
+                            .await?;
+                    next.extend(relevant_leaves(&dir, *end, &ranges));
+                    if !cached {
+                        let offset = (self.header.leaf_offset + entry.offset) as usize;
+                        self.cache.insert_dir(offset, dir).await;
+                        inserted += 1;
+                    }
+                }
+            }
+            pending = next;
+        }
+
+        Ok(inserted)
+    }
+
+    /// Loads the leaf directories for the tiles most likely to be requested after `z/x/y` into the
+    /// directory cache: its four neighbors at the same zoom level, its four children, then the
+    /// diagonal neighbors, as when a map is panned or zoomed in.
+    ///
+    /// At most `budget` of these tiles are looked up, skipping those outside the archive's bounds.
+    /// The budget limits tile lookups, not directory fetches: a lookup may read a leaf directory
+    /// at each level of the directory tree, and lookups served by the cache count against it too.
+    /// Since the reader is cheap to clone, this is best run in a background task after serving
+    /// a tile, so the next request finds its directories cached, or by `get_tile` itself, see
+    /// [`Self::with_neighbor_prefetch`]. This only does useful work with a real [`DirectoryCache`],
+    /// not with [`NoCache`].
+    pub async fn prefetch_neighbors(&self, z: u8, x: u64, y: u64, budget: usize) -> PmtResult<()> {
+        let neighbors = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+            .into_iter()
+            .map(|(dx, dy)| (Some(z), x.checked_add_signed(dx), y.checked_add_signed(dy)))
+            .chain(
+                [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .into_iter()
+                    .map(|(dx, dy)| {
+                        let child = |v: u64, d| v.checked_mul(2).map(|v| v + d);
+                        (z.checked_add(1), child(x, dx), child(y, dy))
+                    }),
+            )
+            .chain(
+                [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+                    .into_iter()
+                    .map(|(dx, dy)| (Some(z), x.checked_add_signed(dx), y.checked_add_signed(dy))),
+            )
+            .filter_map(|(z, x, y)| TileCoord::new(z?, x?, y?).ok())
+            .filter(|&coord| self.header.contains_tile(coord));
+
+        for coord in neighbors.take(budget) {
+            self.find_tile_entry(TileId::from(coord).value()).await?;
+        }
+        Ok(())
+    }
+
+    /// Tile IDs of all tiles present at `zoom`, read from the directories without fetching tile data.
+    #[cfg(feature = "roaring")]
+    pub async fn coverage(&self, zoom: u8) -> PmtResult<roaring::RoaringTreemap> {
+        let ranges = tile_id_ranges(None, zoom..=zoom);
+        let mut coverage = roaring::RoaringTreemap::new();
+        let Some(zoom_ids) = ranges.first() else {
+            return Ok(coverage);
+        };
+
+        for entry in self.tile_entries_in(&ranges).await? {
+            let start = entry.tile_id.max(zoom_ids.start);
+            let end = (entry.tile_id + u64::from(entry.run_length)).min(zoom_ids.end);
+            coverage.insert_range(start..end);
+        }
+        Ok(coverage)
+    }
+
+    /// Tile-aligned bounding box of all tiles present at `zoom`, or `None` if there are none.
+    pub async fn coverage_bbox(&self, zoom: u8) -> PmtResult<Option<BoundingBox>> {
+        let ranges = tile_id_ranges(None, zoom..=zoom);
+        let Some(zoom_ids) = ranges.first() else {
+            return Ok(None);
+        };
+
+        let mut extent: Option<(u64, u64, u64, u64)> = None;
+        for entry in self.tile_entries_in(&ranges).await? {
+            let start = entry.tile_id.max(zoom_ids.start);
+            let end = (entry.tile_id + u64::from(entry.run_length)).min(zoom_ids.end);
+            let Some((x0, y0, x1, y1)) = tile_extent(zoom, start..end) else {
+                continue;
+            };
+            extent = Some(match extent {
+                Some((min_x, min_y, max_x, max_y)) => {
+                    (min_x.min(x0), min_y.min(y0), max_x.max(x1), max_y.max(y1))
+                }
+                None => (x0, y0, x1, y1),
+            });
+        }
+
+        let Some((min_x, min_y, max_x, max_y)) = extent else {
+            return Ok(None);
+        };
+        let (min_lon, _, _, max_lat) = TileCoord::new(zoom, min_x, min_y)?.bounds();
+        let (_, min_lat, max_lon, _) = TileCoord::new(zoom, max_x, max_y)?.bounds();
+        Ok(Some(BoundingBox::new(min_lon, min_lat, max_lon, max_lat)))
+    }
+
+    /// Summary statistics of the tile and leaf directory entries, computed by reading every directory
+    /// in the archive but no tile data.
+    pub async fn stats(&self) -> PmtResult<ArchiveStats> {
+        let (tiles, leaves) = self.entries_in(&tile_id_ranges(None, 0..=MAX_ZOOM)).await?;
+        ArchiveStats::from_entries(&tiles, &leaves)
+    }
+
+    /// Walks all directories of the archive depth-first, returning their entries in directory order
+    /// along with the boundaries of each leaf directory. Reads every directory but no tile data.
+    ///
+    /// Unlike [`stats`](Self::stats), this exposes how entries are split into leaf directories,
+    /// e.g. for tools that analyze or rewrite the directory structure.
+    ///
+    /// All events are collected before returning, so memory use grows with the number of entries
+    /// in the archive, at about 32 bytes per entry. Prefer [`stats`](Self::stats) for large archives
+    /// when the directory structure itself is not needed.
+    /// Fails with [`PmtError::DirectoryTooDeep`] if leaf directories are nested more than 6 levels deep.
+    pub async fn entries_with_leaves(&self) -> PmtResult<Vec<DirectoryEvent>> {
+        let mut events = Vec::new();
+        let mut stack = vec![(Arc::clone(&self.root_directory), 0)];
+        while let Some((dir, index)) = stack.last_mut() {
+            let Some(entry) = dir.get(*index) else {
+                stack.pop();
+                if !stack.is_empty() {
+                    events.push(DirectoryEvent::LeafEnd);
+                }
+                continue;
+            };
+            *index += 1;
+
+            if !entry.is_leaf() {
+                events.push(DirectoryEvent::Tile(entry));
+            } else if stack.len() > MAX_LEAF_DEPTH {
+                return Err(PmtError::DirectoryTooDeep(MAX_LEAF_DEPTH));
+            } else {
+                let depth = stack.len() as u8;
+                let offset = (self.header.leaf_offset + entry.offset) as _;
+                let leaf = self.read_directory(offset, entry.length as _).await?;
+                events.push(DirectoryEvent::LeafStart { entry, depth });
+                stack.push((Arc::new(leaf), 0));
+            }
+        }
+        Ok(events)
+    }
+
+    /// Collects all tile entries whose tile ID span overlaps any of `ranges`, in tile ID order.
+    pub(crate) async fn tile_entries_in(&self, ranges: &[Range<u64>]) -> PmtResult<Vec<DirEntry>> {
+        Ok(self.entries_in(ranges).await?.0)
+    }
+
+    /// Collects all tile entries whose tile ID span overlaps any of `ranges`, in tile ID order,
+    /// reading only the leaf directories that can contain such entries.
+    /// Also returns the entries of the leaf directories that were read.
+    async fn entries_in(&self, ranges: &[Range<u64>]) -> PmtResult<(Vec<DirEntry>, Vec<DirEntry>)> {
+        let mut entries = tile_entries(&self.root_directory, ranges);
+        let mut leaves = Vec::new();
+        let mut pending = relevant_leaves(&self.root_directory, u64::MAX, ranges);
+
+        for _ in 0..MAX_LEAF_DEPTH {
+            let mut next = Vec::new();
+            for (entry, end) in pending {
+                let offset = (self.header.leaf_offset + entry.offset) as _;
+                let dir = self.read_directory(offset, entry.length as _).await?;
+                entries.extend(tile_entries(&dir, ranges));
+                next.extend(relevant_leaves(&dir, end, ranges));
+                leaves.push(entry);
+            }
+            if next.is_empty() {
+                break;
+            }
+            pending = next;
+        }
+
+        entries.sort_by_key(|e| e.tile_id);
+        Ok((entries, leaves))
+    }
+
+    /// Locates a tile in the archive, consulting the entry cache first if enabled.
     async fn find_tile_entry(&self, tile_id: u64) -> PmtResult<Option<DirEntry>> {
+        let Some(entries) = &self.entries else {
+            return self.search_tile_entry(tile_id).await;
+        };
+        let lock = || entries.lock().unwrap_or_else(PoisonError::into_inner);
+        match lock().get(tile_id) {
+            DirCacheResult::Found(entry) => return Ok(Some(entry)),
+            DirCacheResult::NotFound => return Ok(None),
+            DirCacheResult::NotCached => {}
+        }
+        let entry = self.search_tile_entry(tile_id).await?;
+        lock().insert(tile_id, entry);
+        Ok(entry)
+    }
+
+    /// Recursively locates a tile in the archive's directories.
+    async fn search_tile_entry(&self, tile_id: u64) -> PmtResult<Option<DirEntry>> {
         let entry = self.root_directory.find_tile_id(tile_id);
         if let Some(entry) = entry {
             if entry.is_leaf() {
-                return self.find_entry_rec(tile_id, entry, 0).await;
+                return self.find_entry_rec(tile_id, &entry, 0).await;
             }
         }
 
-        Ok(entry.cloned())
+        Ok(entry)
     }
 
     async fn find_entry_rec(
@@ -159,12 +782,26 @@ impl<B: AsyncBackend + Sync + Send, C: DirectoryCache + Sync + Send> AsyncPmTile
         // and it allows directory to be cached later without cloning it first.
         let offset = (self.header.leaf_offset + entry.offset) as _;
 
-        let entry = match self.cache.get_dir_entry(offset, tile_id).await {
+        let cached = self.cache.get_dir_entry(offset, tile_id).await;
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            offset,
+            hit = !matches!(cached, DirCacheResult::NotCached),
+            "directory cache lookup"
+        );
+        #[cfg(feature = "metrics")]
+        if matches!(cached, DirCacheResult::NotCached) {
+            metrics::counter!("pmtiles_directory_cache_misses_total").increment(1);
+        } else {
+            metrics::counter!("pmtiles_directory_cache_hits_total").increment(1);
+        }
+
+        let entry = match cached {
             DirCacheResult::NotCached => {
                 // Cache miss - read from backend
                 let length = entry.length as _;
                 let dir = self.read_directory(offset, length).await?;
-                let entry = dir.find_tile_id(tile_id).cloned();
+                let entry = dir.find_tile_id(tile_id);
                 self.cache.insert_dir(offset, dir).await;
                 entry
             }
@@ -185,8 +822,50 @@ impl<B: AsyncBackend + Sync + Send, C: DirectoryCache + Sync + Send> AsyncPmTile
         Ok(entry)
     }
 
+    /// Reads exactly `length` bytes, using the bytes prefetched at open time when they cover the range.
+    async fn read_exact(
+        &self,
+        offset: usize,
+        length: usize,
+        purpose: ReadPurpose,
+    ) -> PmtResult<Bytes> {
+        match offset.checked_add(length) {
+            Some(end) if end <= self.prefetched.len() => {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(offset, length, "read served from prefetched bytes");
+                Ok(self.prefetched.slice(offset..end))
+            }
+            _ => {
+                #[cfg(any(feature = "tracing", feature = "metrics"))]
+                let start = Instant::now();
+                let result = self
+                    .backend
+                    .read_exact(offset, length)
+                    .await
+                    .map_err(|e| backend_error(&*self.backend, e, offset, length));
+                self.io.record(purpose, &result);
+                if matches!(&result, Err(e) if is_archive_changed(e)) {
+                    self.invalidate_cache().await;
+                }
+                #[cfg(feature = "metrics")]
+                record_backend_read(&result, start.elapsed());
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    offset,
+                    length,
+                    duration = ?start.elapsed(),
+                    ok = result.is_ok(),
+                    "backend read"
+                );
+                result
+            }
+        }
+    }
+
     async fn read_directory(&self, offset: usize, length: usize) -> PmtResult<Directory> {
-        let data = self.backend.read_exact(offset, length).await?;
+        let data = self
+            .read_exact(offset, length, ReadPurpose::Directory)
+            .await?;
         Self::read_compressed_directory(self.header.internal_compression, data).await
     }
 
@@ -194,25 +873,142 @@ impl<B: AsyncBackend + Sync + Send, C: DirectoryCache + Sync + Send> AsyncPmTile
         compression: Compression,
         bytes: Bytes,
     ) -> PmtResult<Directory> {
-        let decompressed_bytes = Self::decompress(compression, bytes).await?;
-        Directory::try_from(decompressed_bytes)
+        #[cfg(feature = "tracing")]
+        let (start, length) = (Instant::now(), bytes.len());
+        let decompressed_bytes = decompress(compression, bytes).await?;
+        let directory = Directory::try_from(decompressed_bytes)?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            length,
+            entries = directory.len(),
+            duration = ?start.elapsed(),
+            "decoded directory"
+        );
+        Ok(directory)
+    }
+}
+
+#[cfg(feature = "tilejson")]
+fn parse_vector_layers(metadata: &str) -> PmtResult<Vec<tilejson::VectorLayer>> {
+    let mut meta: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(metadata).map_err(|_| PmtError::InvalidMetadata)?;
+    match meta.remove("vector_layers") {
+        Some(layers) => serde_json::from_value(layers).map_err(|_| PmtError::InvalidMetadata),
+        None => Ok(Vec::new()),
     }
+}
 
-    async fn decompress(compression: Compression, bytes: Bytes) -> PmtResult<Bytes> {
-        let mut decompressed_bytes = Vec::with_capacity(bytes.len() * 2);
-        match compression {
-            Compression::Gzip => {
-                async_compression::tokio::bufread::GzipDecoder::new(&bytes[..])
-                    .read_to_end(&mut decompressed_bytes)
-                    .await?;
-            }
-            v => Err(UnsupportedCompression(v))?,
-        }
+/// Whether the backend reported that the archive has been replaced.
+fn is_archive_changed(error: &PmtError) -> bool {
+    match error {
+        PmtError::ArchiveChanged => true,
+        PmtError::Backend(e) => matches!(e.source, PmtError::ArchiveChanged),
+        _ => false,
+    }
+}
+
+/// Awaits `future`, failing with [`PmtError::Timeout`] once `deadline` has passed.
+async fn until<T>(
+    deadline: Option<Instant>,
+    future: impl Future<Output = PmtResult<T>>,
+) -> PmtResult<T> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline.into(), future)
+            .await
+            .map_err(|_| PmtError::Timeout)?,
+        None => future.await,
+    }
+}
+
+fn backend_error<B: AsyncBackend>(
+    backend: &B,
+    source: PmtError,
+    offset: usize,
+    length: usize,
+) -> PmtError {
+    PmtError::Backend(Box::new(BackendError {
+        resource: backend.resource(),
+        offset,
+        length,
+        source,
+    }))
+}
 
-        Ok(Bytes::from(decompressed_bytes))
+#[cfg(feature = "metrics")]
+fn record_backend_read(result: &PmtResult<Bytes>, duration: Duration) {
+    metrics::counter!("pmtiles_backend_requests_total").increment(1);
+    metrics::histogram!("pmtiles_backend_read_duration_seconds").record(duration);
+    match result {
+        Ok(bytes) => metrics::counter!("pmtiles_backend_bytes_read").increment(bytes.len() as u64),
+        Err(_) => metrics::counter!("pmtiles_backend_errors_total").increment(1),
     }
 }
 
+/// Leaf pointers in `dir` whose tile ID span overlaps any of `ranges`, paired with the end of their span.
+/// The last entry's span ends at `end`, the end of the span covered by `dir` itself.
+fn relevant_leaves(dir: &Directory, end: u64, ranges: &[Range<u64>]) -> Vec<(DirEntry, u64)> {
+    let ends = dir
+        .iter()
+        .skip(1)
+        .map(|e| e.tile_id)
+        .chain(std::iter::once(end));
+    dir.iter()
+        .zip(ends)
+        .filter(|(entry, end)| entry.is_leaf() && overlaps_any(ranges, &(entry.tile_id..*end)))
+        .collect()
+}
+
+/// Tile entries in `dir` whose tile ID span overlaps any of `ranges`.
+fn tile_entries(dir: &Directory, ranges: &[Range<u64>]) -> Vec<DirEntry> {
+    dir.iter()
+        .filter(|e| {
+            !e.is_leaf() && overlaps_any(ranges, &(e.tile_id..e.tile_id + u64::from(e.run_length)))
+        })
+        .collect()
+}
+
+/// Deepest nesting of leaf directories that is followed, counted from the root directory.
+/// Matches the depth limit of `find_entry_rec`.
+pub(crate) const MAX_LEAF_DEPTH: usize = 6;
+
+/// An item of the directory tree, as returned by [`AsyncPmTilesReader::entries_with_leaves`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectoryEvent {
+    /// A leaf directory starts. Following items up to the matching [`LeafEnd`](Self::LeafEnd)
+    /// are its entries. `entry` is the pointer to it, with the offset relative to the leaf
+    /// directory section, and `depth` is 1 for leaves referenced by the root directory.
+    LeafStart { entry: DirEntry, depth: u8 },
+    /// The most recently started leaf directory ends.
+    LeafEnd,
+    /// A tile entry.
+    Tile(DirEntry),
+}
+
+/// Result of [`AsyncPmTilesReader::health_check`].
+#[derive(Debug)]
+pub enum HealthStatus {
+    /// The archive is reachable and its header is unchanged.
+    Ok,
+    /// The archive's header differs from the one read when the reader was opened,
+    /// so the archive has been replaced and the reader should be recreated.
+    Changed,
+    /// The archive could not be read.
+    Unreachable(PmtError),
+}
+
+/// Tile data being read from a backend, see [`AsyncPmTilesReader::get_tile_stream`].
+pub type TileStream = Box<dyn AsyncRead + Send + Unpin>;
+
+/// How reads from a backend are expected to be made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccessPattern {
+    /// Scattered reads, as made when serving individual tiles.
+    #[default]
+    Random,
+    /// Mostly increasing offsets, as made when walking all entries or exporting an archive.
+    Sequential,
+}
+
 pub trait AsyncBackend {
     /// Reads exactly `length` bytes starting at `offset`
     fn read_exact(
@@ -239,14 +1035,69 @@ pub trait AsyncBackend {
 
     /// Reads up to `length` bytes starting at `offset`.
     fn read(&self, offset: usize, length: usize) -> impl Future<Output = PmtResult<Bytes>> + Send;
+
+    /// Streams `length` bytes starting at `offset`, failing while reading if fewer are available.
+    ///
+    /// The default implementation reads all bytes with [`read_exact`](Self::read_exact) first.
+    /// Backends able to pass data on as it arrives should override it.
+    fn read_stream(
+        &self,
+        offset: usize,
+        length: usize,
+    ) -> impl Future<Output = PmtResult<TileStream>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let data = self.read_exact(offset, length).await?;
+            Ok(Box::new(std::io::Cursor::new(data)) as TileStream)
+        }
+    }
+
+    /// Describes the archive this backend reads from, such as its URL or path, for error reporting.
+    fn resource(&self) -> Option<String> {
+        None
+    }
+
+    /// Total size of the archive in bytes, if known.
+    /// Remote backends may only know it once a read has completed.
+    fn size(&self) -> Option<u64> {
+        None
+    }
+
+    /// Tells the backend how upcoming reads are going to be made, so it can adjust
+    /// e.g. how much it reads ahead. Purely advisory, the default implementation ignores it.
+    fn hint(&self, _pattern: AccessPattern) {}
+}
+
+/// Parses the total size from a `Content-Range` header value such as `bytes 0-1023/4096`.
+#[cfg(any(
+    feature = "http-async",
+    feature = "__async-s3",
+    feature = "__async-aws-s3"
+))]
+pub(crate) fn content_range_size(value: &str) -> Option<u64> {
+    value.rsplit_once('/')?.1.trim().parse().ok()
 }
 
 #[cfg(test)]
 #[cfg(feature = "mmap-async-tokio")]
 mod tests {
-    use super::AsyncPmTilesReader;
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    use bytes::Bytes;
+    use tokio::io::AsyncReadExt as _;
+
+    use super::{AsyncBackend, AsyncPmTilesReader, DirectoryEvent, HealthStatus};
+    use crate::cache::{DirCacheResult, DirectoryCache, HashMapCache, NoCache};
+    use crate::codec::decompress;
+    use crate::header::HEADER_SIZE;
     use crate::tests::{RASTER_FILE, VECTOR_FILE};
-    use crate::MmapBackend;
+    use crate::{
+        BoundingBox, Compression, DirEntry, Directory, MmapBackend, PmtError, PmtResult, TileCoord,
+    };
 
     #[tokio::test]
     async fn open_sanity_check() {
@@ -304,6 +1155,27 @@ mod tests {
         assert!(tile.is_ok_and(|t| t.is_some()));
     }
 
+    #[tokio::test]
+    async fn test_leaf_directories() {
+        let backend = MmapBackend::try_from("fixtures/leaf.pmtiles")
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert_eq!(tiles.leaf_count(), 1);
+
+        let root = tiles.root_directory();
+        let leaf_pointer = root.iter().find(DirEntry::is_leaf).unwrap();
+        let leaf = tiles.read_leaf_directory(&leaf_pointer).await.unwrap();
+        assert_eq!(leaf.len(), 5);
+        assert!(leaf.iter().all(|e| !e.is_leaf()));
+
+        let tile_entry = leaf.get(0).unwrap();
+        assert!(matches!(
+            tiles.read_leaf_directory(&tile_entry).await,
+            Err(PmtError::InvalidEntry)
+        ));
+    }
+
     #[tokio::test]
     async fn test_get_metadata() {
         let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
@@ -333,6 +1205,698 @@ mod tests {
         assert!(tj.other.is_empty());
     }
 
+    #[tokio::test]
+    #[cfg(feature = "tilejson")]
+    async fn test_vector_layers() {
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert!(tiles.vector_layers().await.unwrap().is_empty());
+
+        let layers = super::parse_vector_layers(
+            r#"{"vector_layers":[{"id":"roads","fields":{"name":"String","lanes":"Number"},"minzoom":4}]}"#,
+        )
+        .unwrap();
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].id, "roads");
+        assert_eq!(layers[0].fields["lanes"], "Number");
+        assert_eq!(layers[0].minzoom, Some(4));
+
+        assert!(super::parse_vector_layers(r#"{"vector_layers":{}}"#).is_err());
+    }
+
+    struct CountingBackend {
+        inner: MmapBackend,
+        reads: AtomicUsize,
+    }
+
+    impl AsyncBackend for CountingBackend {
+        async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            self.inner.read(offset, length).await
+        }
+    }
+
+    /// Serves an archive that can be modified or taken offline by setting `mode`.
+    struct SwappableBackend {
+        inner: MmapBackend,
+        mode: AtomicUsize,
+    }
+
+    impl SwappableBackend {
+        const CHANGED: usize = 1;
+        const OFFLINE: usize = 2;
+        const REPLACED: usize = 3;
+    }
+
+    impl AsyncBackend for SwappableBackend {
+        async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+            let data = self.inner.read(offset, length).await?;
+            match self.mode.load(Ordering::Relaxed) {
+                Self::CHANGED => {
+                    let mut data = data.to_vec();
+                    data[HEADER_SIZE - 1] ^= 1;
+                    Ok(data.into())
+                }
+                Self::OFFLINE => Err(std::io::Error::from(std::io::ErrorKind::NotFound).into()),
+                Self::REPLACED => Err(PmtError::ArchiveChanged),
+                _ => Ok(data),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_metadata_raw() {
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let (raw, compression) = tiles.get_metadata_raw().await.unwrap();
+        assert_eq!(compression, Compression::Gzip);
+        assert_eq!(raw.len() as u64, tiles.get_header().metadata_length());
+
+        let decompressed = decompress(compression, raw).await.unwrap();
+        assert_eq!(decompressed, tiles.get_metadata().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_health_check() {
+        let backend = SwappableBackend {
+            inner: MmapBackend::try_from(VECTOR_FILE).await.unwrap(),
+            mode: AtomicUsize::new(0),
+        };
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert!(matches!(tiles.health_check().await, HealthStatus::Ok));
+
+        tiles
+            .backend
+            .mode
+            .store(SwappableBackend::CHANGED, Ordering::Relaxed);
+        assert!(matches!(tiles.health_check().await, HealthStatus::Changed));
+
+        tiles
+            .backend
+            .mode
+            .store(SwappableBackend::OFFLINE, Ordering::Relaxed);
+        let HealthStatus::Unreachable(PmtError::Backend(err)) = tiles.health_check().await else {
+            panic!("expected a backend error");
+        };
+        assert!(err.is_not_found());
+        assert_eq!(tiles.io_stats().header.requests, 4);
+    }
+
+    /// Counts lookups in a [`HashMapCache`].
+    #[derive(Default)]
+    struct CountingCache {
+        inner: HashMapCache,
+        lookups: AtomicUsize,
+    }
+
+    impl DirectoryCache for CountingCache {
+        async fn get_dir_entry(&self, offset: usize, tile_id: u64) -> DirCacheResult {
+            self.lookups.fetch_add(1, Ordering::Relaxed);
+            self.inner.get_dir_entry(offset, tile_id).await
+        }
+
+        async fn insert_dir(&self, offset: usize, directory: Directory) {
+            self.inner.insert_dir(offset, directory).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entry_cache() {
+        let backend = MmapBackend::try_from("fixtures/leaf.pmtiles")
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_cached_source(backend, CountingCache::default())
+            .await
+            .unwrap()
+            .with_entry_cache(NonZeroUsize::new(1).unwrap());
+        let lookups = || tiles.cache.lookups.load(Ordering::Relaxed);
+
+        for _ in 0..3 {
+            assert_eq!(tiles.get_tile(1, 1, 0).await.unwrap().unwrap(), &b"4"[..]);
+        }
+        assert_eq!(lookups(), 1);
+
+        // Missing tiles are remembered too, and evict the least recently used entry
+        for _ in 0..3 {
+            assert!(tiles.get_tile(2, 0, 0).await.unwrap().is_none());
+        }
+        assert_eq!(lookups(), 2);
+        tiles.clone().get_tile(1, 1, 0).await.unwrap();
+        assert_eq!(lookups(), 3);
+
+        tiles.invalidate_cache().await;
+        tiles.get_tile(1, 1, 0).await.unwrap();
+        assert_eq!(lookups(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_warm_from() {
+        let open = || async {
+            let backend = MmapBackend::try_from("fixtures/leaf.pmtiles")
+                .await
+                .unwrap();
+            AsyncPmTilesReader::try_from_cached_source(backend, CountingCache::default())
+                .await
+                .unwrap()
+                .with_entry_cache(NonZeroUsize::new(8).unwrap())
+        };
+        let previous = open().await;
+        let tiles = open().await;
+        assert_eq!(tiles.warm_from(&previous).await.unwrap(), 0);
+
+        previous.get_tile(0, 0, 0).await.unwrap();
+        previous.get_tile(1, 1, 0).await.unwrap();
+        assert_eq!(tiles.warm_from(&previous).await.unwrap(), 2);
+        assert_eq!(tiles.cache.lookups.load(Ordering::Relaxed), 2);
+
+        // Warmed tiles are served from the entry cache
+        assert_eq!(tiles.get_tile(1, 1, 0).await.unwrap().unwrap(), &b"4"[..]);
+        tiles.get_tile(0, 0, 0).await.unwrap();
+        assert_eq!(tiles.cache.lookups.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cache_invalidation() {
+        let backend = SwappableBackend {
+            inner: MmapBackend::try_from(VECTOR_FILE).await.unwrap(),
+            mode: AtomicUsize::new(0),
+        };
+        let tiles = AsyncPmTilesReader::try_from_cached_source(backend, HashMapCache::default())
+            .await
+            .unwrap();
+        let cached = || tiles.cache.cache.read().unwrap().len();
+        let fill_cache = || tiles.cache.insert_dir(0, tiles.root_directory().clone());
+
+        fill_cache().await;
+        tiles.invalidate_cache().await;
+        assert_eq!(cached(), 0);
+
+        fill_cache().await;
+        tiles
+            .backend
+            .mode
+            .store(SwappableBackend::CHANGED, Ordering::Relaxed);
+        assert!(matches!(tiles.health_check().await, HealthStatus::Changed));
+        assert_eq!(cached(), 0);
+
+        fill_cache().await;
+        tiles
+            .backend
+            .mode
+            .store(SwappableBackend::REPLACED, Ordering::Relaxed);
+        assert!(matches!(tiles.health_check().await, HealthStatus::Changed));
+        assert_eq!(cached(), 0);
+
+        // Tile data of high zoom levels lies outside the prefetched bytes
+        fill_cache().await;
+        let coord = TileCoord::from_lon_lat(14, 11.25, 43.77).unwrap();
+        assert!(tiles.get_tile(14, coord.x(), coord.y()).await.is_err());
+        assert_eq!(cached(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_prefetch() {
+        let backend = CountingBackend {
+            inner: MmapBackend::try_from(VECTOR_FILE).await.unwrap(),
+            reads: AtomicUsize::new(0),
+        };
+        let tiles =
+            AsyncPmTilesReader::try_from_cached_source_with_prefetch(backend, NoCache, 1 << 20)
+                .await
+                .unwrap();
+
+        assert!(!tiles.get_metadata().await.unwrap().is_empty());
+        assert!(tiles.get_tile(12, 2174, 1492).await.unwrap().is_some());
+        assert_eq!(tiles.backend.reads.load(Ordering::Relaxed), 1);
+
+        let backend = CountingBackend {
+            inner: MmapBackend::try_from(VECTOR_FILE).await.unwrap(),
+            reads: AtomicUsize::new(0),
+        };
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert!(tiles.get_tile(12, 2174, 1492).await.unwrap().is_some());
+        assert!(tiles.backend.reads.load(Ordering::Relaxed) > 1);
+    }
+
+    #[tokio::test]
+    async fn test_warm_cache() {
+        let backend = MmapBackend::try_from("fixtures/leaf.pmtiles")
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_cached_source(backend, HashMapCache::default())
+            .await
+            .unwrap();
+
+        // The root directory holds a single leaf pointer covering every tile
+        let bbox = BoundingBox::new(-170.0, -80.0, -100.0, -10.0);
+        assert_eq!(tiles.warm_cache(Some(bbox), 1..=1).await.unwrap(), 1);
+        assert_eq!(tiles.cache.cache.read().unwrap().len(), 1);
+        assert_eq!(tiles.warm_cache(None, 0..=1).await.unwrap(), 0);
+
+        let tile = tiles.get_tile(1, 1, 0).await.unwrap().unwrap();
+        assert_eq!(tile, &b"4"[..]);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "test-utils")]
+    async fn test_warm_cache_nested_leaves() {
+        use crate::test_utils::{ArchiveBuilder, MemoryBackend};
+        use crate::TileType;
+
+        // 85 tiles in 11 leaves, whose pointers are in turn split across two leaves
+        let archive = ArchiveBuilder::new(TileType::Png)
+            .zoom_levels(0..=3)
+            .leaf_size(8)
+            .max_root_entries(2)
+            .build()
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_cached_source(
+            MemoryBackend::new(archive),
+            HashMapCache::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(tiles.leaf_count(), 2);
+
+        // Caches the first leaf of each level
+        tiles.get_tile(0, 0, 0).await.unwrap();
+        assert_eq!(tiles.cache.cache.read().unwrap().len(), 2);
+        assert_eq!(tiles.warm_cache(None, 0..=3).await.unwrap(), 11);
+        assert_eq!(tiles.cache.cache.read().unwrap().len(), 13);
+        assert_eq!(tiles.warm_cache(None, 0..=3).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "test-utils")]
+    async fn test_prefetch_neighbors() {
+        use crate::test_utils::{ArchiveBuilder, MemoryBackend};
+        use crate::{TileId, TileType};
+
+        // Leaves of four entries each, so tile IDs 87 and 88 are neighbors in different leaves
+        let archive = ArchiveBuilder::new(TileType::Png)
+            .zoom_levels(0..=4)
+            .leaf_size(4)
+            .build()
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_cached_source(
+            MemoryBackend::new(archive),
+            HashMapCache::default(),
+        )
+        .await
+        .unwrap();
+        let cached = || tiles.cache.cache.read().unwrap().len();
+
+        let tile = TileCoord::from(TileId::new(87).unwrap());
+        let neighbor = TileCoord::from(TileId::new(88).unwrap());
+        tiles.get_tile(tile.z(), tile.x(), tile.y()).await.unwrap();
+        assert_eq!(cached(), 1);
+
+        tiles
+            .prefetch_neighbors(tile.z(), tile.x(), tile.y(), 4)
+            .await
+            .unwrap();
+        let prefetched = cached();
+        assert!(prefetched > 1);
+        tiles
+            .get_tile(neighbor.z(), neighbor.x(), neighbor.y())
+            .await
+            .unwrap();
+        assert_eq!(cached(), prefetched);
+
+        // Zoom levels beyond the archive are fine, as are ones without children
+        tiles.prefetch_neighbors(u8::MAX, 0, 0, 12).await.unwrap();
+        assert_eq!(cached(), prefetched);
+
+        // The same lookups happen automatically once enabled, but only for tiles that exist
+        tiles.invalidate_cache().await;
+        let tiles = tiles.with_neighbor_prefetch(4);
+        assert!(tiles.get_tile(5, 0, 0).await.unwrap().is_none());
+        assert_eq!(tiles.cache.cache.read().unwrap().len(), 1);
+        tiles.invalidate_cache().await;
+        tiles.get_tile(tile.z(), tile.x(), tile.y()).await.unwrap();
+        assert_eq!(tiles.cache.cache.read().unwrap().len(), prefetched);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "test-utils")]
+    async fn test_bounds_check() {
+        use crate::test_utils::{ArchiveBuilder, MemoryBackend};
+        use crate::TileType;
+
+        // Large enough that the last leaf directory is not among the bytes read up front
+        let archive = ArchiveBuilder::new(TileType::Png)
+            .zoom_levels(0..=7)
+            .leaf_size(64)
+            .build()
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(MemoryBackend::new(archive))
+            .await
+            .unwrap();
+        let checked = tiles.clone().with_bounds_check();
+
+        assert_eq!(checked.get_tile(7, 1, 2).await.unwrap().unwrap(), "7/1/2");
+        let directory_reads = tiles.io_stats().directory.requests;
+        assert!(checked.get_tile(8, 0, 0).await.unwrap().is_none());
+        assert!(checked.get_tile(2, 4, 0).await.unwrap().is_none());
+        assert_eq!(tiles.io_stats().directory.requests, directory_reads);
+
+        assert!(tiles.get_tile(8, 0, 0).await.unwrap().is_none());
+        assert!(tiles.io_stats().directory.requests > directory_reads);
+    }
+
+    #[tokio::test]
+    async fn test_coverage_bbox() {
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let bbox = tiles.coverage_bbox(0).await.unwrap().unwrap();
+        assert_eq!((bbox.min_lon, bbox.max_lon), (-180.0, 180.0));
+
+        let header = tiles.get_header();
+        let bbox = tiles.coverage_bbox(14).await.unwrap().unwrap();
+        assert!(bbox.min_lon <= header.min_longitude);
+        assert!(bbox.max_lon >= header.max_longitude);
+        assert!(bbox.min_lat <= header.min_latitude);
+        assert!(bbox.max_lat >= header.max_latitude);
+        assert!(bbox.max_lon - bbox.min_lon < 1.0);
+
+        assert!(tiles.coverage_bbox(15).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "test-utils")]
+    async fn test_coverage_bbox_long_run() {
+        use crate::test_utils::MemoryBackend;
+        use crate::{TileId, TileType};
+
+        // A single run over the first quarter of zoom 16, i.e. all of its tiles in the north-western quadrant
+        let first = TileId::new(0).unwrap().descendants_range(16).unwrap().start;
+        let run = DirEntry::new(first, 0, 1, 1 << 30);
+        let mut root = Vec::new();
+        Directory::from_entries(&[run])
+            .unwrap()
+            .write_to(&mut root)
+            .unwrap();
+        let root_offset = HEADER_SIZE as u64;
+        let metadata_offset = root_offset + root.len() as u64;
+        let header = crate::Header::builder(TileType::Png, Compression::None)
+            .internal_compression(Compression::None)
+            .root_directory(root_offset, root.len() as u64)
+            .metadata(metadata_offset, 2)
+            .tile_data(metadata_offset + 2, 1)
+            .zoom_range(16, 16)
+            .build();
+        let mut archive = Vec::new();
+        header.write_to(&mut archive).unwrap();
+        archive.extend_from_slice(&root);
+        archive.extend_from_slice(b"{}x");
+
+        let tiles = AsyncPmTilesReader::try_from_source(MemoryBackend::new(archive))
+            .await
+            .unwrap();
+        let bbox = tiles.coverage_bbox(16).await.unwrap().unwrap();
+        assert_eq!((bbox.min_lon, bbox.max_lon), (-180.0, 0.0));
+        assert!(bbox.min_lat.abs() < 1e-9, "{bbox:?}");
+        assert!(bbox.max_lat > 85.0, "{bbox:?}");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "roaring")]
+    async fn test_coverage() {
+        let backend = MmapBackend::try_from("fixtures/leaf.pmtiles")
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        assert_eq!(
+            tiles.coverage(0).await.unwrap().iter().collect::<Vec<_>>(),
+            [0]
+        );
+        assert_eq!(
+            tiles.coverage(1).await.unwrap().iter().collect::<Vec<_>>(),
+            [1, 2, 3, 4]
+        );
+        assert!(tiles.coverage(2).await.unwrap().is_empty());
+
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert_eq!(tiles.coverage(3).await.unwrap().len(), 64);
+    }
+
+    /// Delays every [`AsyncBackend::read_exact`] call, leaving the initial read made when opening alone.
+    struct SlowBackend(MmapBackend);
+
+    impl AsyncBackend for SlowBackend {
+        async fn read_exact(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            self.0.read_exact(offset, length).await
+        }
+
+        async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+            self.0.read(offset, length).await
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout() {
+        let backend = SlowBackend(MmapBackend::try_from(VECTOR_FILE).await.unwrap());
+        let tiles = AsyncPmTilesReader::try_from_source(backend)
+            .await
+            .unwrap()
+            .with_timeout(Duration::from_secs(1));
+        let coord = TileCoord::from_lon_lat(14, 11.25, 43.77).unwrap();
+
+        let err = tiles.get_tile(14, coord.x(), coord.y()).await.unwrap_err();
+        assert!(matches!(err, PmtError::Timeout));
+
+        // Served from the initially read bytes without a backend request
+        tiles.get_metadata().await.unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(20);
+        let tile = tiles.get_tile_with_deadline(14, coord.x(), coord.y(), deadline);
+        assert!(tile.await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_io_stats() {
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let header = tiles.io_stats().header;
+        assert_eq!((header.requests, header.errors), (1, 0));
+
+        // Metadata lies within the initially read bytes, tile data of high zoom levels does not
+        tiles.get_metadata().await.unwrap();
+        let coord = TileCoord::from_lon_lat(14, 11.25, 43.77).unwrap();
+        let tile = tiles.get_tile(14, coord.x(), coord.y()).await.unwrap();
+        let stats = tiles.io_stats();
+        assert_eq!(stats.metadata.requests, 0);
+        assert_eq!(stats.tile.requests, 1);
+        assert_eq!(stats.tile.bytes, tile.unwrap().len() as u64);
+        assert_eq!(stats.total().requests, 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_tile_stream() {
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let coord = TileCoord::from_lon_lat(14, 11.25, 43.77).unwrap();
+        let tile = tiles.get_tile(14, coord.x(), coord.y()).await.unwrap();
+        let tile = decompress(Compression::Gzip, tile.unwrap()).await.unwrap();
+
+        let mut stream = tiles
+            .get_tile_stream(coord.into(), true)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut streamed = Vec::new();
+        stream.read_to_end(&mut streamed).await.unwrap();
+        assert_eq!(streamed, tile);
+        assert_eq!(tiles.io_stats().tile.requests, 2);
+
+        let missing = TileCoord::new(6, 31, 23).unwrap().into();
+        assert!(tiles
+            .get_tile_stream(missing, true)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "test-utils")]
+    async fn test_get_tile_stream_invalid_entry() {
+        use crate::test_utils::MemoryBackend;
+        use crate::{TileId, TileType};
+
+        let mut root = Vec::new();
+        Directory::from_entries(&[DirEntry::new(0, u64::MAX - 10, 100, 1)])
+            .unwrap()
+            .write_to(&mut root)
+            .unwrap();
+        let root_offset = HEADER_SIZE as u64;
+        let metadata_offset = root_offset + root.len() as u64;
+        let header = crate::Header::builder(TileType::Png, Compression::None)
+            .internal_compression(Compression::None)
+            .root_directory(root_offset, root.len() as u64)
+            .metadata(metadata_offset, 2)
+            .tile_data(metadata_offset + 2, 0)
+            .build();
+        let mut archive = Vec::new();
+        header.write_to(&mut archive).unwrap();
+        archive.extend_from_slice(&root);
+        archive.extend_from_slice(b"{}");
+
+        let tiles = AsyncPmTilesReader::try_from_source(MemoryBackend::new(archive))
+            .await
+            .unwrap();
+        let result = tiles.get_tile_stream(TileId::new(0).unwrap(), false).await;
+        assert!(matches!(result, Err(PmtError::InvalidEntry)));
+    }
+
+    #[tokio::test]
+    async fn test_get_tile_info() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let (tile, compression, content_type) = tiles
+            .get_tile_info(TileCoord::new(0, 0, 0).unwrap().into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tile, &include_bytes!("../fixtures/0_0_0.png")[..]);
+        assert_eq!(compression, Compression::None);
+        assert_eq!(content_type, "image/png");
+
+        let missing = TileCoord::new(6, 31, 23).unwrap().into();
+        assert!(tiles.get_tile_info(missing).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_clone() {
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_cached_source(backend, HashMapCache::default())
+            .await
+            .unwrap();
+        let coord = TileCoord::from_lon_lat(14, 11.25, 43.77).unwrap();
+
+        let handle = tiles.clone().with_timeout(Duration::from_secs(10));
+        let tile = tokio::spawn(async move { handle.get_tile(14, coord.x(), coord.y()).await })
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(tile.is_some());
+        assert!(tiles.timeout.is_none());
+
+        // The clone's reads and cached directories are shared
+        assert_eq!(tiles.io_stats().tile.requests, 1);
+        let directory_requests = tiles.io_stats().directory.requests;
+        tiles.get_tile(14, coord.x(), coord.y()).await.unwrap();
+        assert_eq!(tiles.io_stats().directory.requests, directory_requests);
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        for file in [RASTER_FILE, VECTOR_FILE, "fixtures/leaf.pmtiles"] {
+            let backend = MmapBackend::try_from(file).await.unwrap();
+            let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+            let header = tiles.get_header();
+
+            let stats = tiles.stats().await.unwrap();
+            let count = |n: Option<std::num::NonZeroU64>| n.map_or(0, std::num::NonZeroU64::get);
+            assert_eq!(stats.addressed_tiles, count(header.n_addressed_tiles()));
+            assert_eq!(stats.tile_entries, count(header.n_tile_entries()));
+            assert_eq!(stats.unique_tiles, count(header.n_tile_contents()));
+            assert_eq!(
+                stats.tiles_per_zoom.values().sum::<u64>(),
+                stats.addressed_tiles
+            );
+            assert_eq!(stats.tile_sizes.total, header.data_length);
+            assert_eq!(stats.leaf_directory_sizes.total, header.leaf_length);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entries_with_leaves() {
+        for file in [RASTER_FILE, VECTOR_FILE, "fixtures/leaf.pmtiles"] {
+            let backend = MmapBackend::try_from(file).await.unwrap();
+            let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+            let stats = tiles.stats().await.unwrap();
+            let events = tiles.entries_with_leaves().await.unwrap();
+
+            let mut depth = 0;
+            let (mut tile_entries, mut leaves, mut leaf_length) = (0, 0, 0);
+            let mut last_tile_id = None;
+            for event in events {
+                match event {
+                    DirectoryEvent::LeafStart { entry, depth: d } => {
+                        depth += 1;
+                        assert_eq!(d, depth);
+                        leaves += 1;
+                        leaf_length += u64::from(entry.length());
+                    }
+                    DirectoryEvent::LeafEnd => depth -= 1,
+                    DirectoryEvent::Tile(entry) => {
+                        assert!(last_tile_id < Some(entry.tile_id()));
+                        last_tile_id = Some(entry.tile_id());
+                        tile_entries += 1;
+                    }
+                }
+            }
+            assert_eq!(depth, 0);
+            assert_eq!(tile_entries, stats.tile_entries);
+            assert_eq!(leaves, stats.leaf_directories);
+            assert_eq!(leaf_length, tiles.get_header().leaf_length);
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "test-utils")]
+    async fn test_entries_with_leaves_too_deep() {
+        use crate::test_utils::MemoryBackend;
+        use crate::TileType;
+
+        // A leaf directory pointing to itself
+        let mut leaf = Vec::new();
+        let mut leaf_length = 0;
+        for _ in 0..2 {
+            leaf.clear();
+            Directory::from_entries(&[DirEntry::new(0, 0, leaf_length, 0)])
+                .unwrap()
+                .write_to(&mut leaf)
+                .unwrap();
+            leaf_length = leaf.len() as u32;
+        }
+        let mut root = Vec::new();
+        Directory::from_entries(&[DirEntry::new(0, 0, leaf_length, 0)])
+            .unwrap()
+            .write_to(&mut root)
+            .unwrap();
+        let root_offset = HEADER_SIZE as u64;
+        let metadata_offset = root_offset + root.len() as u64;
+        let leaf_offset = metadata_offset + 2;
+        let header = crate::Header::builder(TileType::Png, Compression::None)
+            .internal_compression(Compression::None)
+            .root_directory(root_offset, root.len() as u64)
+            .metadata(metadata_offset, 2)
+            .leaf_directories(leaf_offset, leaf.len() as u64)
+            .tile_data(leaf_offset + leaf.len() as u64, 0)
+            .build();
+        let mut archive = Vec::new();
+        header.write_to(&mut archive).unwrap();
+        archive.extend_from_slice(&root);
+        archive.extend_from_slice(b"{}");
+        archive.extend_from_slice(&leaf);
+
+        let tiles = AsyncPmTilesReader::try_from_source(MemoryBackend::new(archive))
+            .await
+            .unwrap();
+        let result = tiles.entries_with_leaves().await;
+        assert!(matches!(result, Err(PmtError::DirectoryTooDeep(6))));
+    }
+
     #[tokio::test]
     async fn test_martin_675() {
         let backend = MmapBackend::try_from("fixtures/leaf.pmtiles")
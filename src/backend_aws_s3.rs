@@ -1,5 +1,7 @@
+use std::sync::OnceLock;
+
 use crate::{
-    async_reader::{AsyncBackend, AsyncPmTilesReader},
+    async_reader::{content_range_size, AsyncBackend, AsyncPmTilesReader},
     cache::{DirectoryCache, NoCache},
     PmtError, PmtResult,
 };
@@ -46,6 +48,7 @@ pub struct AwsS3Backend {
     client: Client,
     bucket: String,
     key: String,
+    size: OnceLock<u64>,
 }
 
 impl AwsS3Backend {
@@ -55,6 +58,7 @@ impl AwsS3Backend {
             client,
             bucket,
             key,
+            size: OnceLock::new(),
         }
     }
 }
@@ -72,6 +76,9 @@ impl AsyncBackend for AwsS3Backend {
             .range(range)
             .send()
             .await?;
+        if let Some(size) = obj.content_range().and_then(content_range_size) {
+            self.size.get_or_init(|| size);
+        }
 
         let response_bytes = obj
             .body
@@ -86,4 +93,12 @@ impl AsyncBackend for AwsS3Backend {
             Ok(response_bytes)
         }
     }
+
+    fn resource(&self) -> Option<String> {
+        Some(format!("s3://{}/{}", self.bucket, self.key))
+    }
+
+    fn size(&self) -> Option<u64> {
+        self.size.get().copied()
+    }
 }
@@ -0,0 +1,187 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use bytes::Bytes;
+
+use crate::async_reader::{AccessPattern, AsyncBackend, AsyncPmTilesReader, TileStream};
+use crate::cache::{DirectoryCache, NoCache};
+use crate::error::{PmtError, PmtResult};
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+impl AsyncPmTilesReader<BoxedBackend, NoCache> {
+    /// Creates a new `PMTiles` reader from any backend, erasing its type.
+    ///
+    /// Readers created this way all have the same type, regardless of the backend chosen at runtime.
+    pub async fn new_with_backend<B>(backend: B) -> PmtResult<Self>
+    where
+        B: AsyncBackend + Send + Sync + 'static,
+    {
+        Self::new_with_cached_backend(NoCache, backend).await
+    }
+
+    /// Opens the archive at a local path or URL, see [`BoxedBackend::open`].
+    pub async fn open(location: &str) -> PmtResult<Self> {
+        Self::open_with_cache(NoCache, location).await
+    }
+}
+
+impl<C: DirectoryCache + Sync + Send> AsyncPmTilesReader<BoxedBackend, C> {
+    /// Creates a new cached `PMTiles` reader from any backend, erasing its type.
+    pub async fn new_with_cached_backend<B>(cache: C, backend: B) -> PmtResult<Self>
+    where
+        B: AsyncBackend + Send + Sync + 'static,
+    {
+        Self::try_from_cached_source(BoxedBackend::new(backend), cache).await
+    }
+
+    /// Opens the archive at a local path or URL with a cache, see [`BoxedBackend::open`].
+    pub async fn open_with_cache(cache: C, location: &str) -> PmtResult<Self> {
+        let backend = BoxedBackend::open(location).await?;
+        Self::try_from_cached_source(backend, cache).await
+    }
+}
+
+/// A type-erased [`AsyncBackend`], for choosing between backends at runtime.
+///
+/// [`AsyncBackend`] returns `impl Future`, so it cannot be used as a trait object directly.
+/// This wrapper boxes the futures returned by the wrapped backend instead, at the cost of
+/// one allocation per read.
+pub struct BoxedBackend(Box<dyn DynBackend>);
+
+impl BoxedBackend {
+    pub fn new<B>(backend: B) -> Self
+    where
+        B: AsyncBackend + Send + Sync + 'static,
+    {
+        Self(Box::new(backend))
+    }
+
+    /// Creates the backend for a local path or URL, chosen by its scheme:
+    /// `http://` and `https://` URLs are read with [`HttpBackend`](crate::HttpBackend)
+    /// using a default client, and `file://` URLs and plain paths with
+    /// [`MmapBackend`](crate::MmapBackend).
+    ///
+    /// Fails with [`PmtError::UnsupportedLocation`] if the backend needed is not enabled.
+    #[cfg_attr(not(feature = "mmap-async-tokio"), allow(clippy::unused_async))]
+    pub async fn open(location: &str) -> PmtResult<Self> {
+        let scheme = location
+            .split_once("://")
+            .map(|(scheme, _)| scheme.to_ascii_lowercase());
+        match scheme.as_deref() {
+            #[cfg(feature = "http-async")]
+            Some("http" | "https") => Ok(Self::new(crate::HttpBackend::try_from(
+                reqwest::Client::new(),
+                location,
+            )?)),
+            #[cfg(feature = "mmap-async-tokio")]
+            None | Some("file") => {
+                let path = location
+                    .split_once("://")
+                    .map_or(location, |(_, path)| path);
+                Ok(Self::new(crate::MmapBackend::try_from(path).await?))
+            }
+            _ => Err(PmtError::UnsupportedLocation(location.to_string())),
+        }
+    }
+}
+
+impl AsyncBackend for BoxedBackend {
+    async fn read_exact(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        self.0.read_exact(offset, length).await
+    }
+
+    async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        self.0.read(offset, length).await
+    }
+
+    async fn read_stream(&self, offset: usize, length: usize) -> PmtResult<TileStream> {
+        self.0.read_stream(offset, length).await
+    }
+
+    fn resource(&self) -> Option<String> {
+        self.0.resource()
+    }
+
+    fn size(&self) -> Option<u64> {
+        self.0.size()
+    }
+
+    fn hint(&self, pattern: AccessPattern) {
+        self.0.hint(pattern);
+    }
+}
+
+/// Object-safe counterpart of [`AsyncBackend`], implemented for every backend.
+trait DynBackend: Send + Sync {
+    fn read_exact(&self, offset: usize, length: usize) -> BoxFuture<'_, PmtResult<Bytes>>;
+    fn read(&self, offset: usize, length: usize) -> BoxFuture<'_, PmtResult<Bytes>>;
+    fn read_stream(&self, offset: usize, length: usize) -> BoxFuture<'_, PmtResult<TileStream>>;
+    fn resource(&self) -> Option<String>;
+    fn size(&self) -> Option<u64>;
+    fn hint(&self, pattern: AccessPattern);
+}
+
+impl<B: AsyncBackend + Send + Sync> DynBackend for B {
+    fn read_exact(&self, offset: usize, length: usize) -> BoxFuture<'_, PmtResult<Bytes>> {
+        Box::pin(AsyncBackend::read_exact(self, offset, length))
+    }
+
+    fn read(&self, offset: usize, length: usize) -> BoxFuture<'_, PmtResult<Bytes>> {
+        Box::pin(AsyncBackend::read(self, offset, length))
+    }
+
+    fn read_stream(&self, offset: usize, length: usize) -> BoxFuture<'_, PmtResult<TileStream>> {
+        Box::pin(AsyncBackend::read_stream(self, offset, length))
+    }
+
+    fn resource(&self) -> Option<String> {
+        AsyncBackend::resource(self)
+    }
+
+    fn size(&self) -> Option<u64> {
+        AsyncBackend::size(self)
+    }
+
+    fn hint(&self, pattern: AccessPattern) {
+        AsyncBackend::hint(self, pattern);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mmap-async-tokio")]
+mod tests {
+    use super::BoxedBackend;
+    use crate::async_reader::{AsyncBackend, AsyncPmTilesReader};
+    use crate::tests::{RASTER_FILE, VECTOR_FILE};
+    use crate::{MmapBackend, PmtError};
+
+    #[tokio::test]
+    async fn runtime_backend_choice() {
+        let mut readers: Vec<AsyncPmTilesReader<BoxedBackend>> = Vec::new();
+        for path in [RASTER_FILE, VECTOR_FILE] {
+            let backend = MmapBackend::try_from(path).await.unwrap();
+            readers.push(AsyncPmTilesReader::new_with_backend(backend).await.unwrap());
+        }
+
+        let fixture_tile = include_bytes!("../fixtures/0_0_0.png");
+        let tile = readers[0].get_tile(0, 0, 0).await.unwrap().unwrap();
+        assert_eq!(tile, &fixture_tile[..]);
+        assert!(readers[1].get_tile(0, 0, 0).await.unwrap().is_some());
+
+        let backend = BoxedBackend::new(MmapBackend::try_from(RASTER_FILE).await.unwrap());
+        assert_eq!(backend.resource().as_deref(), Some(RASTER_FILE));
+        assert_eq!(backend.size(), Some(716_052));
+    }
+
+    #[tokio::test]
+    async fn open_location() {
+        for location in [RASTER_FILE.to_string(), format!("FILE://{RASTER_FILE}")] {
+            let tiles = AsyncPmTilesReader::open(&location).await.unwrap();
+            assert!(tiles.get_tile(0, 0, 0).await.unwrap().is_some());
+        }
+
+        let result = AsyncPmTilesReader::open("gs://bucket/archive.pmtiles").await;
+        assert!(matches!(result, Err(PmtError::UnsupportedLocation(_))));
+    }
+}
@@ -0,0 +1,136 @@
+use std::io;
+use std::num::NonZeroUsize;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::async_reader::{AccessPattern, AsyncBackend, TileStream};
+use crate::error::PmtResult;
+
+/// Wraps another backend, limiting how many reads may be in flight at the same time.
+///
+/// Further reads wait until an earlier one has completed, so bursts of tile requests don't turn into
+/// bursts of backend requests. Streams returned by [`AsyncBackend::read_stream`] count against
+/// the limit until they are dropped. Wrap a [`ThrottledBackend`](crate::ThrottledBackend) to also
+/// limit the request rate, or the other way around to only count reads that were let through.
+pub struct ConcurrencyLimitBackend<B> {
+    inner: B,
+    permits: Arc<Semaphore>,
+}
+
+impl<B> ConcurrencyLimitBackend<B> {
+    /// Wraps `inner`, allowing at most `limit` reads in flight.
+    pub fn new(inner: B, limit: NonZeroUsize) -> Self {
+        Self {
+            inner,
+            permits: Arc::new(Semaphore::new(limit.get())),
+        }
+    }
+
+    /// Number of further reads that can start without waiting.
+    pub fn available(&self) -> usize {
+        self.permits.available_permits()
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    async fn acquire(&self) -> OwnedSemaphorePermit {
+        match Arc::clone(&self.permits).acquire_owned().await {
+            Ok(permit) => permit,
+            Err(_) => unreachable!("the semaphore is never closed"),
+        }
+    }
+}
+
+impl<B: AsyncBackend + Sync + Send> AsyncBackend for ConcurrencyLimitBackend<B> {
+    async fn read_exact(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        let _permit = self.acquire().await;
+        self.inner.read_exact(offset, length).await
+    }
+
+    async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        let _permit = self.acquire().await;
+        self.inner.read(offset, length).await
+    }
+
+    async fn read_stream(&self, offset: usize, length: usize) -> PmtResult<TileStream> {
+        let permit = self.acquire().await;
+        let stream = self.inner.read_stream(offset, length).await?;
+        Ok(Box::new(PermitStream {
+            stream,
+            _permit: permit,
+        }))
+    }
+
+    fn resource(&self) -> Option<String> {
+        self.inner.resource()
+    }
+
+    fn size(&self) -> Option<u64> {
+        self.inner.size()
+    }
+
+    fn hint(&self, pattern: AccessPattern) {
+        self.inner.hint(pattern);
+    }
+}
+
+/// A stream holding on to its permit until it is dropped.
+struct PermitStream {
+    stream: TileStream,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl AsyncRead for PermitStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mmap-async-tokio")]
+mod tests {
+    use std::num::NonZeroUsize;
+    use std::time::Duration;
+
+    use tokio::io::AsyncReadExt as _;
+
+    use super::ConcurrencyLimitBackend;
+    use crate::async_reader::AsyncBackend;
+    use crate::tests::RASTER_FILE;
+    use crate::MmapBackend;
+
+    #[tokio::test(start_paused = true)]
+    async fn limits_in_flight_reads() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let backend = ConcurrencyLimitBackend::new(backend, NonZeroUsize::new(2).unwrap());
+
+        let mut first = backend.read_stream(0, 127).await.unwrap();
+        let second = backend.read_stream(127, 127).await.unwrap();
+        assert_eq!(backend.available(), 0);
+
+        // A third read has to wait for one of the streams to be dropped
+        let third = tokio::time::timeout(Duration::from_secs(1), backend.read_exact(0, 127));
+        assert!(third.await.is_err());
+
+        let mut header = Vec::new();
+        first.read_to_end(&mut header).await.unwrap();
+        assert_eq!(&header[..7], b"PMTiles");
+        drop(first);
+        assert_eq!(backend.available(), 1);
+        backend.read_exact(0, 127).await.unwrap();
+
+        drop(second);
+        assert_eq!(backend.available(), 2);
+    }
+}
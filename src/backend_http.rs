@@ -1,8 +1,21 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::task::{Context, Poll};
+
 use bytes::Bytes;
-use reqwest::header::{HeaderValue, RANGE};
-use reqwest::{Client, IntoUrl, Method, Request, StatusCode, Url};
+use reqwest::header::{
+    HeaderMap, HeaderValue, CONTENT_RANGE, ETAG, IF_MATCH, IF_UNMODIFIED_SINCE, LAST_MODIFIED,
+    RANGE,
+};
+use reqwest::{Client, IntoUrl, Method, Request, Response, StatusCode, Url};
+use tokio::io::{AsyncRead, ReadBuf};
 
-use crate::async_reader::{AsyncBackend, AsyncPmTilesReader};
+use crate::async_reader::{
+    content_range_size, AccessPattern, AsyncBackend, AsyncPmTilesReader, TileStream,
+};
 use crate::cache::{DirectoryCache, NoCache};
 use crate::error::PmtResult;
 use crate::PmtError;
@@ -31,9 +44,68 @@ impl<C: DirectoryCache + Sync + Send> AsyncPmTilesReader<HttpBackend, C> {
     }
 }
 
+/// Reads archives over HTTP(S) with range requests.
+///
+/// The `ETag` (or failing that, `Last-Modified`) header of the first response is kept,
+/// and later requests are made conditional on it. Reads fail with [`PmtError::ArchiveChanged`]
+/// once the server reports a different version of the file, as offsets read from the previous
+/// version's directories would no longer point to the right data.
+///
+/// While hinted with [`AccessPattern::Sequential`], requests are enlarged to at least
+/// 1 MiB, and following reads are served from the surplus where possible.
 pub struct HttpBackend {
     client: Client,
     url: Url,
+    validator: OnceLock<Validator>,
+    size: OnceLock<u64>,
+    sequential: AtomicBool,
+    read_ahead: Mutex<Option<(usize, Bytes)>>,
+}
+
+/// Minimum number of bytes requested at once during sequential access.
+const READ_AHEAD: usize = 1024 * 1024;
+
+/// Identifies the version of the remote file.
+#[derive(Debug, Clone)]
+enum Validator {
+    ETag(HeaderValue),
+    LastModified(HeaderValue),
+}
+
+impl Validator {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get(ETAG)
+            .map(|v| Self::ETag(v.clone()))
+            .or_else(|| {
+                headers
+                    .get(LAST_MODIFIED)
+                    .map(|v| Self::LastModified(v.clone()))
+            })
+    }
+
+    /// Whether `other` identifies a different version of the file than `self`.
+    fn conflicts_with(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::ETag(a), Self::ETag(b)) | (Self::LastModified(a), Self::LastModified(b)) => {
+                a != b
+            }
+            _ => false,
+        }
+    }
+
+    fn add_precondition(&self, headers: &mut HeaderMap) {
+        match self {
+            // Weak ETags never match with `If-Match`, so these are only compared after the fact
+            Self::ETag(etag) if etag.as_bytes().starts_with(b"W/") => {}
+            Self::ETag(etag) => {
+                headers.insert(IF_MATCH, etag.clone());
+            }
+            Self::LastModified(date) => {
+                headers.insert(IF_UNMODIFIED_SINCE, date.clone());
+            }
+        }
+    }
 }
 
 impl HttpBackend {
@@ -41,36 +113,183 @@ impl HttpBackend {
         Ok(HttpBackend {
             client,
             url: url.into_url()?,
+            validator: OnceLock::new(),
+            size: OnceLock::new(),
+            sequential: AtomicBool::new(false),
+            read_ahead: Mutex::new(None),
         })
     }
-}
 
-impl AsyncBackend for HttpBackend {
-    async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+    /// Returns the requested range if it was read ahead by a previous request.
+    fn read_ahead(&self, offset: usize, length: usize) -> Option<Bytes> {
+        let buffer = self
+            .read_ahead
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let (start, data) = buffer.as_ref()?;
+        let begin = offset.checked_sub(*start)?;
+        let end = begin.checked_add(length)?;
+        (end <= data.len()).then(|| data.slice(begin..end))
+    }
+
+    async fn fetch(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        let response_bytes = self.request(offset, length).await?.bytes().await?;
+        if response_bytes.len() > length {
+            Err(PmtError::ResponseBodyTooLong(response_bytes.len(), length))
+        } else {
+            Ok(response_bytes)
+        }
+    }
+
+    /// Sends a range request, checking that the server honored the range
+    /// and still serves the same version of the archive.
+    async fn request(&self, offset: usize, length: usize) -> PmtResult<Response> {
         let end = offset + length - 1;
         let range = format!("bytes={offset}-{end}");
         let range = HeaderValue::try_from(range)?;
 
         let mut req = Request::new(Method::GET, self.url.clone());
         req.headers_mut().insert(RANGE, range);
+        if let Some(validator) = self.validator.get() {
+            validator.add_precondition(req.headers_mut());
+        }
 
-        let response = self.client.execute(req).await?.error_for_status()?;
+        let response = self.client.execute(req).await?;
+        if response.status() == StatusCode::PRECONDITION_FAILED {
+            return Err(PmtError::ArchiveChanged);
+        }
+        let response = response.error_for_status()?;
         if response.status() != StatusCode::PARTIAL_CONTENT {
             return Err(PmtError::RangeRequestsUnsupported);
         }
 
-        let response_bytes = response.bytes().await?;
-        if response_bytes.len() > length {
-            Err(PmtError::ResponseBodyTooLong(response_bytes.len(), length))
-        } else {
-            Ok(response_bytes)
+        if let Some(size) = response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(content_range_size)
+        {
+            self.size.get_or_init(|| size);
+        }
+        if let Some(validator) = Validator::from_headers(response.headers()) {
+            let expected = self.validator.get_or_init(|| validator.clone());
+            if expected.conflicts_with(&validator) {
+                return Err(PmtError::ArchiveChanged);
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+impl AsyncBackend for HttpBackend {
+    async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        if !self.sequential.load(Ordering::Relaxed) {
+            return self.fetch(offset, length).await;
+        }
+        if let Some(data) = self.read_ahead(offset, length) {
+            return Ok(data);
+        }
+
+        let data = self.fetch(offset, length.max(READ_AHEAD)).await?;
+        let requested = data.slice(..length.min(data.len()));
+        *self
+            .read_ahead
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some((offset, data));
+        Ok(requested)
+    }
+
+    async fn read_stream(&self, offset: usize, length: usize) -> PmtResult<TileStream> {
+        let response = self.request(offset, length).await?;
+        Ok(Box::new(ResponseReader {
+            response: Some(response),
+            pending: None,
+            chunk: Bytes::new(),
+            remaining: length,
+        }))
+    }
+
+    fn hint(&self, pattern: AccessPattern) {
+        let sequential = pattern == AccessPattern::Sequential;
+        self.sequential.store(sequential, Ordering::Relaxed);
+        if !sequential {
+            *self
+                .read_ahead
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = None;
         }
     }
+
+    fn resource(&self) -> Option<String> {
+        Some(self.url.to_string())
+    }
+
+    fn size(&self) -> Option<u64> {
+        self.size.get().copied()
+    }
+}
+
+type ChunkFuture = Pin<Box<dyn Future<Output = (Response, reqwest::Result<Option<Bytes>>)> + Send>>;
+
+/// Passes on the body of a response as it arrives, ending after `remaining` bytes.
+struct ResponseReader {
+    response: Option<Response>,
+    pending: Option<ChunkFuture>,
+    chunk: Bytes,
+    remaining: usize,
+}
+
+impl AsyncRead for ResponseReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        while this.chunk.is_empty() && this.remaining > 0 {
+            let mut pending = if let Some(pending) = this.pending.take() {
+                pending
+            } else {
+                let Some(mut response) = this.response.take() else {
+                    return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+                };
+                Box::pin(async move {
+                    let chunk = response.chunk().await;
+                    (response, chunk)
+                })
+            };
+            let Poll::Ready((response, chunk)) = pending.as_mut().poll(cx) else {
+                this.pending = Some(pending);
+                return Poll::Pending;
+            };
+            match chunk {
+                Ok(Some(chunk)) => {
+                    this.response = Some(response);
+                    this.chunk = chunk;
+                }
+                Ok(None) => return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+                Err(e) => return Poll::Ready(Err(io::Error::other(e))),
+            }
+        }
+
+        let n = this.chunk.len().min(this.remaining).min(buf.remaining());
+        buf.put_slice(&this.chunk.split_to(n));
+        this.remaining -= n;
+        Poll::Ready(Ok(()))
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+    use tokio::net::TcpListener;
+
     use super::*;
+    use crate::{TileCoord, TileId};
 
     static TEST_URL: &str =
         "https://protomaps.github.io/PMTiles/protomaps(vector)ODbL_firenze.pmtiles";
@@ -82,4 +301,123 @@ mod tests {
 
         AsyncPmTilesReader::try_from_source(backend).await.unwrap();
     }
+
+    /// Serves `data` with range requests on a local port, sending the current value of `etag` as `ETag`
+    /// and honoring `If-Match`. Returns the URL to request and the number of requests served.
+    async fn serve(data: Vec<u8>, etag: Arc<Mutex<String>>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/archive.pmtiles", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::Relaxed);
+                let mut request = Vec::new();
+                while !request.ends_with(b"\r\n\r\n") {
+                    let mut buf = [0; 1024];
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8(request).unwrap().to_lowercase();
+                let header = |name: &str| {
+                    request
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .map(|value| value.trim().to_string())
+                };
+
+                let etag = etag.lock().unwrap().clone();
+                let response = if header("if-match:").is_some_and(|v| v != etag) {
+                    "HTTP/1.1 412 Precondition Failed\r\ncontent-length: 0\r\n\r\n"
+                        .as_bytes()
+                        .to_vec()
+                } else {
+                    let range = header("range: bytes=").unwrap();
+                    let (start, end) = range.split_once('-').unwrap();
+                    let start: usize = start.parse().unwrap();
+                    let end = end.parse::<usize>().unwrap().min(data.len() - 1);
+                    let body = &data[start..=end];
+                    let mut response = format!(
+                        "HTTP/1.1 206 Partial Content\r\ncontent-range: bytes {start}-{end}/{}\r\n\
+                         etag: {etag}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                        data.len(),
+                        body.len()
+                    )
+                    .into_bytes();
+                    response.extend_from_slice(body);
+                    response
+                };
+                stream.write_all(&response).await.unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn detects_changed_archive() {
+        let data = std::fs::read(crate::tests::RASTER_FILE).unwrap();
+        let etag = Arc::new(Mutex::new("\"v1\"".to_string()));
+        let (url, _) = serve(data, etag.clone()).await;
+
+        let backend = HttpBackend::try_from(Client::new(), url).unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert!(tiles.get_tile(3, 4, 4).await.unwrap().is_some());
+
+        *etag.lock().unwrap() = "\"v2\"".to_string();
+        let err = tiles.get_tile(3, 4, 4).await.unwrap_err();
+        let PmtError::Backend(err) = err else {
+            panic!("unexpected error {err}");
+        };
+        assert!(matches!(err.source, PmtError::ArchiveChanged));
+    }
+
+    #[tokio::test]
+    async fn detects_truncated_archive() {
+        let mut data = std::fs::read(crate::tests::RASTER_FILE).unwrap();
+        data.truncate(data.len() - 100);
+        let size = data.len() as u64;
+        let (url, _) = serve(data, Arc::new(Mutex::new("\"v1\"".to_string()))).await;
+
+        let backend = HttpBackend::try_from(Client::new(), url).unwrap();
+        let result = AsyncPmTilesReader::try_from_source(backend).await;
+        assert!(matches!(result, Err(PmtError::ArchiveTruncated(_, actual)) if actual == size));
+    }
+
+    #[tokio::test]
+    async fn sequential_read_ahead() {
+        let data = std::fs::read(crate::tests::RASTER_FILE).unwrap();
+        let (url, requests) = serve(data.clone(), Arc::new(Mutex::new("\"v1\"".to_string()))).await;
+        let backend = HttpBackend::try_from(Client::new(), url).unwrap();
+
+        backend.hint(AccessPattern::Sequential);
+        for offset in (0..data.len() - 100).step_by(1000) {
+            let chunk = backend.read_exact(offset, 100).await.unwrap();
+            assert_eq!(chunk, data[offset..offset + 100]);
+        }
+        assert_eq!(requests.load(Ordering::Relaxed), 1);
+
+        backend.hint(AccessPattern::Random);
+        backend.read_exact(0, 100).await.unwrap();
+        backend.read_exact(100, 100).await.unwrap();
+        assert_eq!(requests.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn stream_tile() {
+        let data = std::fs::read(crate::tests::RASTER_FILE).unwrap();
+        let (url, _) = serve(data, Arc::new(Mutex::new("\"v1\"".to_string()))).await;
+        let backend = HttpBackend::try_from(Client::new(), url).unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let tile_id = TileId::from(TileCoord::new(0, 0, 0).unwrap());
+        let mut stream = tiles
+            .get_tile_stream(tile_id, false)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut streamed = Vec::new();
+        stream.read_to_end(&mut streamed).await.unwrap();
+        assert_eq!(streamed, include_bytes!("../fixtures/0_0_0.png"));
+    }
 }
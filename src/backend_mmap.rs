@@ -1,5 +1,5 @@
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use bytes::{Buf, Bytes};
 use fmmap::tokio::{AsyncMmapFile, AsyncMmapFileExt as _, AsyncOptions};
@@ -30,14 +30,16 @@ impl<C: DirectoryCache + Sync + Send> AsyncPmTilesReader<MmapBackend, C> {
 
 pub struct MmapBackend {
     file: AsyncMmapFile,
+    path: PathBuf,
 }
 
 impl MmapBackend {
     pub async fn try_from<P: AsRef<Path>>(p: P) -> PmtResult<Self> {
         Ok(Self {
-            file: AsyncMmapFile::open_with_options(p, AsyncOptions::new().read(true))
+            file: AsyncMmapFile::open_with_options(p.as_ref(), AsyncOptions::new().read(true))
                 .await
                 .map_err(|_| PmtError::UnableToOpenMmapFile)?,
+            path: p.as_ref().to_path_buf(),
         })
     }
 }
@@ -66,4 +68,12 @@ impl AsyncBackend for MmapBackend {
 
         Ok(self.file.reader(offset)?.copy_to_bytes(read_length))
     }
+
+    fn resource(&self) -> Option<String> {
+        Some(self.path.display().to_string())
+    }
+
+    fn size(&self) -> Option<u64> {
+        Some(self.file.len() as u64)
+    }
 }
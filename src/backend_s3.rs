@@ -1,8 +1,10 @@
+use std::sync::OnceLock;
+
 use bytes::Bytes;
 use s3::Bucket;
 
 use crate::{
-    async_reader::{AsyncBackend, AsyncPmTilesReader},
+    async_reader::{content_range_size, AsyncBackend, AsyncPmTilesReader},
     cache::{DirectoryCache, NoCache},
     error::PmtError::ResponseBodyTooLong,
     PmtResult,
@@ -38,12 +40,17 @@ impl<C: DirectoryCache + Sync + Send> AsyncPmTilesReader<S3Backend, C> {
 pub struct S3Backend {
     bucket: Bucket,
     path: String,
+    size: OnceLock<u64>,
 }
 
 impl S3Backend {
     #[must_use]
     pub fn from(bucket: Bucket, path: String) -> S3Backend {
-        Self { bucket, path }
+        Self {
+            bucket,
+            path,
+            size: OnceLock::new(),
+        }
     }
 }
 
@@ -58,6 +65,14 @@ impl AsyncBackend for S3Backend {
             )
             .await?;
 
+        if let Some(size) = response
+            .headers()
+            .get("content-range")
+            .and_then(|v| content_range_size(v))
+        {
+            self.size.get_or_init(|| size);
+        }
+
         let response_bytes = response.bytes();
 
         if response_bytes.len() > length {
@@ -66,4 +81,12 @@ impl AsyncBackend for S3Backend {
             Ok(response_bytes.clone())
         }
     }
+
+    fn resource(&self) -> Option<String> {
+        Some(format!("s3://{}/{}", self.bucket.name(), self.path))
+    }
+
+    fn size(&self) -> Option<u64> {
+        self.size.get().copied()
+    }
 }
@@ -0,0 +1,179 @@
+use std::num::{NonZeroU32, NonZeroU64};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::time::Instant;
+
+use crate::async_reader::{AccessPattern, AsyncBackend, TileStream};
+use crate::error::PmtResult;
+
+/// Wraps another backend, limiting the rate of requests and bytes requested from it.
+///
+/// Limits are enforced with token buckets that allow bursts of up to one second's worth of requests
+/// or bytes. A read that exceeds the budget waits until enough tokens have accumulated;
+/// reads larger than the whole budget are let through after waiting for the deficit.
+/// Waiting requires the Tokio runtime to have its time driver enabled.
+pub struct ThrottledBackend<B> {
+    inner: B,
+    requests: Option<Mutex<TokenBucket>>,
+    bytes: Option<Mutex<TokenBucket>>,
+}
+
+impl<B> ThrottledBackend<B> {
+    /// Wraps `inner` without any limits, see [`Self::requests_per_second`] and [`Self::bytes_per_second`].
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            requests: None,
+            bytes: None,
+        }
+    }
+
+    /// Limits the number of reads per second.
+    #[must_use]
+    pub fn requests_per_second(mut self, rate: NonZeroU32) -> Self {
+        self.requests = Some(Mutex::new(TokenBucket::new(f64::from(rate.get()))));
+        self
+    }
+
+    /// Limits the number of bytes requested per second.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn bytes_per_second(mut self, rate: NonZeroU64) -> Self {
+        self.bytes = Some(Mutex::new(TokenBucket::new(rate.get() as f64)));
+        self
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    /// Waits until both limits allow a read of `length` bytes.
+    #[allow(clippy::cast_precision_loss)]
+    async fn throttle(&self, length: usize) {
+        let delay = [(&self.requests, 1.0), (&self.bytes, length as f64)]
+            .into_iter()
+            .filter_map(|(bucket, amount)| Some(reserve(bucket.as_ref()?, amount)))
+            .max()
+            .unwrap_or_default();
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+fn reserve(bucket: &Mutex<TokenBucket>, amount: f64) -> Duration {
+    // A poisoned lock only means another reservation panicked, the bucket itself is still consistent
+    let mut bucket = bucket
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    bucket.reserve(amount)
+}
+
+impl<B: AsyncBackend + Sync + Send> AsyncBackend for ThrottledBackend<B> {
+    async fn read_exact(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        self.throttle(length).await;
+        self.inner.read_exact(offset, length).await
+    }
+
+    async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        self.throttle(length).await;
+        self.inner.read(offset, length).await
+    }
+
+    async fn read_stream(&self, offset: usize, length: usize) -> PmtResult<TileStream> {
+        self.throttle(length).await;
+        self.inner.read_stream(offset, length).await
+    }
+
+    fn resource(&self) -> Option<String> {
+        self.inner.resource()
+    }
+
+    fn size(&self) -> Option<u64> {
+        self.inner.size()
+    }
+
+    fn hint(&self, pattern: AccessPattern) {
+        self.inner.hint(pattern);
+    }
+}
+
+struct TokenBucket {
+    /// Tokens added per second, which is also the bucket's capacity.
+    rate: f64,
+    /// Available tokens, negative while reservations are waiting for tokens to accumulate.
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            tokens: rate,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Takes `amount` tokens, returning how long the caller has to wait until they are available.
+    fn reserve(&mut self, amount: f64) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate) - amount;
+        self.updated = now;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mmap-async-tokio")]
+mod tests {
+    use std::num::{NonZeroU32, NonZeroU64};
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::ThrottledBackend;
+    use crate::async_reader::AsyncBackend;
+    use crate::tests::RASTER_FILE;
+    use crate::MmapBackend;
+
+    #[tokio::test(start_paused = true)]
+    async fn limits_requests() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let backend =
+            ThrottledBackend::new(backend).requests_per_second(NonZeroU32::new(2).unwrap());
+
+        let start = Instant::now();
+        for _ in 0..5 {
+            backend.read_exact(0, 127).await.unwrap();
+        }
+        // Two reads fit in the initial burst, the other three are spaced 0.5s apart
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(1500), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(1600), "{elapsed:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limits_bytes() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let backend =
+            ThrottledBackend::new(backend).bytes_per_second(NonZeroU64::new(1000).unwrap());
+
+        let start = Instant::now();
+        backend.read(0, 1000).await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        backend.read(0, 3000).await.unwrap();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(3), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(3100), "{elapsed:?}");
+    }
+}
@@ -0,0 +1,352 @@
+#[cfg(any(test, feature = "__async"))]
+use std::ops::{Range, RangeInclusive};
+
+use crate::error::{PmtError, PmtResult};
+use crate::tile::MAX_LATITUDE;
+#[cfg(any(test, feature = "__async"))]
+use crate::tile::{TileCoord, TileId, MAX_ZOOM};
+
+/// Mean radius of the earth, as used for [`BoundingBox::around`].
+const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+/// A geographic bounding box in WGS84 degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lon: f64,
+    pub min_lat: f64,
+    pub max_lon: f64,
+    pub max_lat: f64,
+}
+
+impl BoundingBox {
+    #[must_use]
+    pub fn new(min_lon: f64, min_lat: f64, max_lon: f64, max_lat: f64) -> Self {
+        Self {
+            min_lon,
+            min_lat,
+            max_lon,
+            max_lat,
+        }
+    }
+
+    /// Creates a bounding box, failing with [`PmtError::InvalidBoundingBox`] unless all
+    /// coordinates are valid WGS84 degrees and the minimums do not exceed the maximums.
+    /// Boxes crossing the antimeridian are not supported.
+    pub fn try_new(min_lon: f64, min_lat: f64, max_lon: f64, max_lat: f64) -> PmtResult<Self> {
+        let lon = -180.0..=180.0;
+        let lat = -90.0..=90.0;
+        if lon.contains(&min_lon)
+            && lon.contains(&max_lon)
+            && lat.contains(&min_lat)
+            && lat.contains(&max_lat)
+            && min_lon <= max_lon
+            && min_lat <= max_lat
+        {
+            Ok(Self::new(min_lon, min_lat, max_lon, max_lat))
+        } else {
+            Err(PmtError::InvalidBoundingBox)
+        }
+    }
+
+    /// The box around a point extending `radius_meters` in every direction, e.g. to extract
+    /// an offline map around a location. Distances are measured on a spherical earth.
+    ///
+    /// Boxes reaching a pole span all longitudes, and boxes crossing the antimeridian are cut off at it.
+    /// Fails with [`PmtError::InvalidBoundingBox`] for invalid coordinates or a negative radius.
+    pub fn around(lon: f64, lat: f64, radius_meters: f64) -> PmtResult<Self> {
+        if radius_meters.is_nan() || radius_meters < 0.0 {
+            return Err(PmtError::InvalidBoundingBox);
+        }
+        Self::try_new(lon, lat, lon, lat)?;
+
+        let delta_lat = (radius_meters / EARTH_RADIUS_METERS).to_degrees();
+        let min_lat = (lat - delta_lat).max(-90.0);
+        let max_lat = (lat + delta_lat).min(90.0);
+        if min_lat <= -90.0 || max_lat >= 90.0 {
+            return Self::try_new(-180.0, min_lat, 180.0, max_lat);
+        }
+        // Meridians converge towards the poles, so use the parallel closest to one
+        let widest = min_lat.abs().max(max_lat.abs()).to_radians();
+        let delta_lon = delta_lat / widest.cos();
+        Self::try_new(
+            (lon - delta_lon).max(-180.0),
+            min_lat,
+            (lon + delta_lon).min(180.0),
+            max_lat,
+        )
+    }
+
+    /// Clamps the box to the area covered by web mercator tiles,
+    /// i.e. latitudes within about ±85.05° and longitudes within ±180°.
+    #[must_use]
+    pub fn normalize(self) -> Self {
+        Self {
+            min_lon: self.min_lon.clamp(-180.0, 180.0),
+            min_lat: self.min_lat.clamp(-MAX_LATITUDE, MAX_LATITUDE),
+            max_lon: self.max_lon.clamp(-180.0, 180.0),
+            max_lat: self.max_lat.clamp(-MAX_LATITUDE, MAX_LATITUDE),
+        }
+    }
+
+    /// Parses the envelope of a WKT `POLYGON`, e.g. `POLYGON((11.1 43.7, 11.3 43.7, 11.3 43.8, 11.1 43.7))`.
+    pub fn from_wkt(wkt: &str) -> PmtResult<Self> {
+        let wkt = wkt.trim();
+        let body = wkt
+            .get(.."POLYGON".len())
+            .filter(|keyword| keyword.eq_ignore_ascii_case("POLYGON"))
+            .map(|keyword| wkt[keyword.len()..].trim())
+            .and_then(|rings| rings.strip_prefix('('))
+            .and_then(|rings| rings.strip_suffix(')'))
+            .ok_or(PmtError::InvalidBoundingBox)?;
+
+        let mut points = Vec::new();
+        for ring in body.split("),") {
+            let ring = ring.trim().trim_start_matches('(').trim_end_matches(')');
+            for point in ring.split(',') {
+                let mut ordinates = point.split_whitespace().map(str::parse::<f64>);
+                match (ordinates.next(), ordinates.next()) {
+                    (Some(Ok(lon)), Some(Ok(lat))) => points.push((lon, lat)),
+                    _ => return Err(PmtError::InvalidBoundingBox),
+                }
+            }
+        }
+        Self::envelope(&points)
+    }
+
+    /// Parses a `GeoJSON` `bbox` array, `[min_lon, min_lat, max_lon, max_lat]`,
+    /// or `[min_lon, min_lat, min_z, max_lon, max_lat, max_z]` with elevations, which are ignored.
+    pub fn from_geojson_bbox(bbox: &str) -> PmtResult<Self> {
+        let values = bbox
+            .trim()
+            .strip_prefix('[')
+            .and_then(|values| values.strip_suffix(']'))
+            .ok_or(PmtError::InvalidBoundingBox)?
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| PmtError::InvalidBoundingBox)?;
+        match values[..] {
+            [min_lon, min_lat, max_lon, max_lat] | [min_lon, min_lat, _, max_lon, max_lat, _] => {
+                Self::try_new(min_lon, min_lat, max_lon, max_lat)
+            }
+            _ => Err(PmtError::InvalidBoundingBox),
+        }
+    }
+
+    /// The smallest box containing all `points`.
+    fn envelope(points: &[(f64, f64)]) -> PmtResult<Self> {
+        let (&(lon, lat), rest) = points.split_first().ok_or(PmtError::InvalidBoundingBox)?;
+        let bbox = rest
+            .iter()
+            .fold(Self::new(lon, lat, lon, lat), |b, &(lon, lat)| {
+                Self::new(
+                    b.min_lon.min(lon),
+                    b.min_lat.min(lat),
+                    b.max_lon.max(lon),
+                    b.max_lat.max(lat),
+                )
+            });
+        Self::try_new(bbox.min_lon, bbox.min_lat, bbox.max_lon, bbox.max_lat)
+    }
+
+    /// Whether the tile's extent overlaps this box by more than an edge.
+    #[cfg(any(test, feature = "__async"))]
+    fn intersects_tile(&self, coord: TileCoord) -> bool {
+        let (min_lon, min_lat, max_lon, max_lat) = coord.bounds();
+        min_lon < self.max_lon
+            && max_lon > self.min_lon
+            && min_lat < self.max_lat
+            && max_lat > self.min_lat
+    }
+
+    /// Whether the tile's extent lies completely within this box.
+    #[cfg(any(test, feature = "__async"))]
+    fn contains_tile(&self, coord: TileCoord) -> bool {
+        let (min_lon, min_lat, max_lon, max_lat) = coord.bounds();
+        min_lon >= self.min_lon
+            && max_lon <= self.max_lon
+            && min_lat >= self.min_lat
+            && max_lat <= self.max_lat
+    }
+}
+
+/// Sorted, non-overlapping tile ID ranges of all tiles within `zooms` intersecting `bbox`,
+/// or of whole zoom levels when `bbox` is `None`.
+///
+/// Walks the tile pyramid top-down: subtrees fully inside the box contribute one contiguous
+/// range per zoom level, so only tiles along the box edges are visited individually.
+#[cfg(any(test, feature = "__async"))]
+pub(crate) fn tile_id_ranges(
+    bbox: Option<&BoundingBox>,
+    zooms: RangeInclusive<u8>,
+) -> Vec<Range<u64>> {
+    let min_zoom = *zooms.start();
+    let max_zoom = (*zooms.end()).min(MAX_ZOOM);
+    let mut ranges = Vec::new();
+    if min_zoom > max_zoom {
+        return ranges;
+    }
+
+    let mut stack: Vec<TileId> = TileId::new(0).into_iter().collect();
+    while let Some(tile) = stack.pop() {
+        let coord = TileCoord::from(tile);
+        let (contained, intersects) = match bbox {
+            Some(bbox) => (bbox.contains_tile(coord), bbox.intersects_tile(coord)),
+            None => (true, true),
+        };
+        if contained {
+            let zooms = min_zoom.max(tile.zoom())..=max_zoom;
+            ranges.extend(zooms.filter_map(|z| tile.descendants_range(z)));
+        } else if intersects {
+            if tile.zoom() >= min_zoom {
+                ranges.push(tile.value()..tile.value() + 1);
+            }
+            if tile.zoom() < max_zoom {
+                stack.extend(tile.children().into_iter().flatten());
+            }
+        }
+    }
+
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Whether `range` overlaps any of the sorted, non-overlapping `ranges`.
+#[cfg(any(test, feature = "__async"))]
+pub(crate) fn overlaps_any(ranges: &[Range<u64>], range: &Range<u64>) -> bool {
+    let idx = ranges.partition_point(|r| r.end <= range.start);
+    ranges.get(idx).is_some_and(|r| r.start < range.end)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::float_cmp)]
+    use std::ops::RangeInclusive;
+
+    use super::{overlaps_any, tile_id_ranges, BoundingBox};
+    use crate::tile::{TileCoord, TileId, MAX_LATITUDE};
+    use crate::PmtError;
+
+    #[test]
+    fn whole_zoom_levels() {
+        assert_eq!(tile_id_ranges(None, 0..=2), vec![0..21]);
+        assert_eq!(tile_id_ranges(None, 2..=3), vec![5..85]);
+        assert!(tile_id_ranges(None, RangeInclusive::new(3, 2)).is_empty());
+    }
+
+    #[test]
+    fn bbox_ranges() {
+        // Florence, roughly
+        let bbox = BoundingBox::new(11.15, 43.72, 11.33, 43.83);
+        let ranges = tile_id_ranges(Some(&bbox), 0..=14);
+
+        let inside = TileId::from(TileCoord::from_lon_lat(14, 11.25, 43.77).unwrap());
+        let outside = TileId::from(TileCoord::from_lon_lat(14, 2.35, 48.85).unwrap());
+        assert!(overlaps_any(&ranges, &(inside.value()..inside.value() + 1)));
+        assert!(!overlaps_any(
+            &ranges,
+            &(outside.value()..outside.value() + 1)
+        ));
+        assert!(overlaps_any(&ranges, &(0..1)));
+        assert!(ranges.windows(2).all(|w| w[0].end < w[1].start));
+
+        let tiles_at_14: u64 = ranges
+            .iter()
+            .map(|r| {
+                let z14 = TileId::new(0).unwrap().descendants_range(14).unwrap();
+                r.end.min(z14.end).saturating_sub(r.start.max(z14.start))
+            })
+            .sum();
+        // about 9 x 8 tiles at zoom 14
+        assert!((60..=100).contains(&tiles_at_14), "{tiles_at_14}");
+    }
+
+    #[test]
+    fn validation() {
+        assert!(BoundingBox::try_new(11.15, 43.72, 11.33, 43.83).is_ok());
+        assert!(BoundingBox::try_new(-180.0, -90.0, 180.0, 90.0).is_ok());
+        for (min_lon, min_lat, max_lon, max_lat) in [
+            (11.33, 43.72, 11.15, 43.83),
+            (11.15, 43.83, 11.33, 43.72),
+            (-181.0, 0.0, 0.0, 1.0),
+            (0.0, 0.0, 1.0, 90.5),
+            (f64::NAN, 0.0, 1.0, 1.0),
+        ] {
+            assert!(matches!(
+                BoundingBox::try_new(min_lon, min_lat, max_lon, max_lat),
+                Err(PmtError::InvalidBoundingBox)
+            ));
+        }
+
+        let world = BoundingBox::new(-200.0, -90.0, 180.0, 90.0).normalize();
+        assert_eq!(
+            world,
+            BoundingBox::new(-180.0, -MAX_LATITUDE, 180.0, MAX_LATITUDE)
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let florence = BoundingBox::new(11.15, 43.72, 11.33, 43.83);
+        let wkt = "POLYGON ((11.15 43.72, 11.33 43.72, 11.33 43.83, 11.15 43.83, 11.15 43.72))";
+        assert_eq!(BoundingBox::from_wkt(wkt).unwrap(), florence);
+        let with_hole = "polygon((11.15 43.72,11.33 43.72,11.33 43.83,11.15 43.72),(11.2 43.75,11.3 43.75,11.2 43.8,11.2 43.75))";
+        assert_eq!(BoundingBox::from_wkt(with_hole).unwrap(), florence);
+
+        for json in [
+            "[11.15, 43.72, 11.33, 43.83]",
+            "[11.15,43.72,0,11.33,43.83,100]",
+        ] {
+            assert_eq!(BoundingBox::from_geojson_bbox(json).unwrap(), florence);
+        }
+
+        for wkt in [
+            "POINT (11.15 43.72)",
+            "POLYGON ((11.15 43.72, 11.33))",
+            "POLYGON (())",
+        ] {
+            assert!(BoundingBox::from_wkt(wkt).is_err(), "{wkt}");
+        }
+        for json in [
+            "11.15, 43.72, 11.33, 43.83",
+            "[11.15, 43.72, 11.33]",
+            "[a, b, c, d]",
+        ] {
+            assert!(BoundingBox::from_geojson_bbox(json).is_err(), "{json}");
+        }
+    }
+
+    #[test]
+    fn around_point() {
+        // One degree of latitude is about 111 km
+        let bbox = BoundingBox::around(11.25, 43.77, 111_195.0).unwrap();
+        assert!((bbox.max_lat - 44.77).abs() < 1e-3, "{bbox:?}");
+        assert!((bbox.min_lat - 42.77).abs() < 1e-3, "{bbox:?}");
+        // ...and less than that of longitude at these latitudes
+        assert!(bbox.max_lon - 11.25 > 1.35, "{bbox:?}");
+        assert!((bbox.max_lon - 11.25 - (11.25 - bbox.min_lon)).abs() < 1e-9);
+
+        assert_eq!(
+            BoundingBox::around(11.25, 43.77, 0.0).unwrap(),
+            BoundingBox::new(11.25, 43.77, 11.25, 43.77)
+        );
+        let polar = BoundingBox::around(0.0, 89.5, 100_000.0).unwrap();
+        assert_eq!(
+            (polar.min_lon, polar.max_lon, polar.max_lat),
+            (-180.0, 180.0, 90.0)
+        );
+        let antimeridian = BoundingBox::around(179.9, 0.0, 100_000.0).unwrap();
+        assert_eq!(antimeridian.max_lon, 180.0);
+
+        assert!(BoundingBox::around(11.25, 43.77, -1.0).is_err());
+        assert!(BoundingBox::around(11.25, 43.77, f64::NAN).is_err());
+        assert!(BoundingBox::around(11.25, 95.0, 1.0).is_err());
+    }
+}
@@ -12,8 +12,14 @@ pub enum DirCacheResult {
 
 impl From<Option<&DirEntry>> for DirCacheResult {
     fn from(entry: Option<&DirEntry>) -> Self {
+        entry.copied().into()
+    }
+}
+
+impl From<Option<DirEntry>> for DirCacheResult {
+    fn from(entry: Option<DirEntry>) -> Self {
         match entry {
-            Some(entry) => DirCacheResult::Found(entry.clone()),
+            Some(entry) => DirCacheResult::Found(entry),
             None => DirCacheResult::NotFound,
         }
     }
@@ -31,6 +37,18 @@ pub trait DirectoryCache {
     /// Insert a directory into the cache, using the offset as a key.
     /// Note that cache must be internally mutable.
     fn insert_dir(&self, offset: usize, directory: Directory) -> impl Future<Output = ()> + Send;
+
+    /// Remove the directory stored at `offset`, if any.
+    /// The default implementation does nothing, for caches that cannot remove entries.
+    fn invalidate(&self, _offset: usize) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
+    /// Remove all directories, e.g. because the archive they were read from has been replaced.
+    /// The default implementation does nothing, for caches that cannot remove entries.
+    fn invalidate_all(&self) -> impl Future<Output = ()> + Send {
+        async {}
+    }
 }
 
 pub struct NoCache;
@@ -69,4 +87,16 @@ impl DirectoryCache for HashMapCache {
         #[allow(clippy::unwrap_used)]
         self.cache.write().unwrap().insert(offset, directory);
     }
+
+    async fn invalidate(&self, offset: usize) {
+        // Panic if the lock is poisoned is not something the user can handle
+        #[allow(clippy::unwrap_used)]
+        self.cache.write().unwrap().remove(&offset);
+    }
+
+    async fn invalidate_all(&self) {
+        // Panic if the lock is poisoned is not something the user can handle
+        #[allow(clippy::unwrap_used)]
+        self.cache.write().unwrap().clear();
+    }
 }
@@ -0,0 +1,67 @@
+//! Compression and decompression of tiles, metadata and directories.
+//!
+//! Only [`Compression::None`] and [`Compression::Gzip`] are supported so far,
+//! other compressions fail with [`PmtError::UnsupportedCompression`].
+
+use bytes::Bytes;
+use tokio::io::AsyncReadExt as _;
+
+use crate::error::{PmtError, PmtResult};
+use crate::Compression;
+
+/// Decompresses `bytes` compressed with `compression`, e.g. a tile read with
+/// [`get_tile`](crate::async_reader::AsyncPmTilesReader::get_tile) and the archive's tile compression.
+pub async fn decompress(compression: Compression, bytes: Bytes) -> PmtResult<Bytes> {
+    match compression {
+        Compression::None => Ok(bytes),
+        Compression::Gzip => {
+            let mut decompressed_bytes = Vec::with_capacity(bytes.len() * 2);
+            async_compression::tokio::bufread::GzipDecoder::new(&bytes[..])
+                .read_to_end(&mut decompressed_bytes)
+                .await?;
+            Ok(Bytes::from(decompressed_bytes))
+        }
+        v => Err(PmtError::UnsupportedCompression(v)),
+    }
+}
+
+/// Compresses `bytes` with `compression`, the counterpart of [`decompress`].
+pub async fn compress(compression: Compression, bytes: Bytes) -> PmtResult<Bytes> {
+    match compression {
+        Compression::None => Ok(bytes),
+        Compression::Gzip => {
+            let mut compressed_bytes = Vec::with_capacity(bytes.len());
+            async_compression::tokio::bufread::GzipEncoder::new(&bytes[..])
+                .read_to_end(&mut compressed_bytes)
+                .await?;
+            Ok(Bytes::from(compressed_bytes))
+        }
+        v => Err(PmtError::UnsupportedCompression(v)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::{compress, decompress};
+    use crate::{Compression, PmtError};
+
+    #[tokio::test]
+    async fn round_trip() {
+        let data = Bytes::from_static(b"{\"name\":\"test\",\"name\":\"test\"}");
+        for compression in [Compression::None, Compression::Gzip] {
+            let compressed = compress(compression, data.clone()).await.unwrap();
+            assert_eq!(decompress(compression, compressed).await.unwrap(), data);
+        }
+
+        let compressed = compress(Compression::Gzip, data.clone()).await.unwrap();
+        assert_eq!(&compressed[..2], &[0x1f, 0x8b]);
+
+        let result = decompress(Compression::Brotli, data).await;
+        assert!(matches!(
+            result,
+            Err(PmtError::UnsupportedCompression(Compression::Brotli))
+        ));
+    }
+}
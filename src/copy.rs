@@ -0,0 +1,440 @@
+#![allow(clippy::cast_possible_truncation)]
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant};
+
+use bytes::{Buf as _, Bytes, BytesMut};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::async_reader::{AsyncBackend, MAX_LEAF_DEPTH};
+use crate::codec::decompress;
+use crate::directory::Directory;
+use crate::error::{PmtError, PmtResult};
+use crate::header::{HEADER_SIZE, MAX_INITIAL_BYTES};
+use crate::{Compression, Header, ImageInfo, TileType};
+
+/// Default number of bytes requested from the source backend at a time.
+pub const DEFAULT_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Number of leading tile bytes needed to recognize image formats, see [`ImageInfo::sniff`].
+const TILE_SIGNATURE_LENGTH: usize = 64;
+
+/// Options controlling [`copy_archive`].
+#[derive(Debug, Clone, Copy)]
+pub struct CopyOptions {
+    /// Number of bytes requested from the source backend per read.
+    pub chunk_size: usize,
+    /// Verify the archive structure while copying: the root and leaf directories must decode,
+    /// and every entry must point inside the leaf or tile data section. Leaf directories are
+    /// checked as their bytes stream past, so a broken one fails the copy partway through.
+    pub verify: bool,
+    /// With [`verify`](Self::verify), also check that every tile starts with the signature of the
+    /// header's tile type, failing with [`PmtError::TileTypeMismatch`] otherwise, see [`ImageInfo`].
+    /// Only applies to uncompressed PNG, JPEG, WebP and AVIF tiles. The offsets of tiles that have
+    /// not streamed past yet are kept in memory, so memory use grows with the number of tiles.
+    pub check_tile_types: bool,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            verify: true,
+            check_tile_types: false,
+        }
+    }
+}
+
+/// Summary of a completed [`copy_archive`] call.
+#[derive(Debug, Clone, Copy)]
+pub struct CopyStats {
+    /// Total number of bytes written to the destination.
+    pub bytes_copied: u64,
+    /// Number of read requests made to the source backend.
+    pub requests: u64,
+    /// Wall-clock duration of the copy.
+    pub elapsed: Duration,
+}
+
+impl CopyStats {
+    /// Average throughput of the copy in bytes per second.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn bytes_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.bytes_copied as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Streams a whole `PMTiles` archive from `backend` into `writer` in large chunks.
+///
+/// The archive length is derived from the header, so trailing bytes past the last section are not copied.
+pub async fn copy_archive<B, W>(
+    backend: &B,
+    writer: &mut W,
+    options: CopyOptions,
+) -> PmtResult<CopyStats>
+where
+    B: AsyncBackend + Sync + Send,
+    W: AsyncWrite + Unpin,
+{
+    let start = Instant::now();
+    let chunk_size = options.chunk_size.max(HEADER_SIZE);
+
+    let mut initial_bytes = backend.read(0, MAX_INITIAL_BYTES.max(chunk_size)).await?;
+    if initial_bytes.len() < HEADER_SIZE {
+        return Err(PmtError::InvalidHeader);
+    }
+    let header = Header::try_from_bytes(initial_bytes.slice(..HEADER_SIZE))?;
+    let total_length = header.layout().end() as usize;
+
+    let mut verifier = if options.verify {
+        Some(Verifier::new(&header, &initial_bytes, options.check_tile_types).await?)
+    } else {
+        None
+    };
+
+    initial_bytes.truncate(total_length);
+    if let Some(verifier) = &mut verifier {
+        verifier.feed(backend, &initial_bytes).await?;
+    }
+    writer.write_all(&initial_bytes).await?;
+    let mut offset = initial_bytes.len();
+    let mut requests = 1;
+
+    while offset < total_length {
+        let length = chunk_size.min(total_length - offset);
+        let chunk = backend.read_exact(offset, length).await?;
+        if let Some(verifier) = &mut verifier {
+            verifier.feed(backend, &chunk).await?;
+        }
+        writer.write_all(&chunk).await?;
+        offset += length;
+        requests += 1;
+    }
+    writer.flush().await?;
+
+    Ok(CopyStats {
+        bytes_copied: offset as u64,
+        requests,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// A part of the archive to check once its bytes have streamed past.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct PendingCheck {
+    start: usize,
+    end: usize,
+    kind: CheckKind,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum CheckKind {
+    /// A leaf directory at the given depth below the root directory.
+    Leaf(usize),
+    /// The leading bytes of a tile.
+    TileSignature,
+}
+
+/// Checks the archive's directories, the leaf directories and tiles as their bytes stream past.
+struct Verifier<'a> {
+    header: &'a Header,
+    /// The tile type to check tiles against, if they are checked.
+    tile_type: Option<TileType>,
+    /// Parts left to check, lowest offset first.
+    pending: BinaryHeap<Reverse<PendingCheck>>,
+    /// The last streamed bytes, from the start of the next pending check on.
+    window: BytesMut,
+    /// Number of bytes streamed so far.
+    received: usize,
+}
+
+impl<'a> Verifier<'a> {
+    /// Checks the root directory, which must be within `initial_bytes`.
+    async fn new(
+        header: &'a Header,
+        initial_bytes: &Bytes,
+        check_tile_types: bool,
+    ) -> PmtResult<Self> {
+        let root_start = header.root_offset as usize;
+        let root_end = root_start
+            .checked_add(header.root_length as usize)
+            .ok_or(PmtError::InvalidHeader)?;
+        if root_start < HEADER_SIZE || root_end > initial_bytes.len() {
+            return Err(PmtError::InvalidHeader);
+        }
+
+        let images = matches!(
+            header.tile_type,
+            TileType::Png | TileType::Jpeg | TileType::Webp | TileType::Avif
+        );
+        let mut verifier = Self {
+            header,
+            tile_type: (check_tile_types && images && header.tile_compression == Compression::None)
+                .then_some(header.tile_type),
+            pending: BinaryHeap::new(),
+            window: BytesMut::new(),
+            received: 0,
+        };
+        verifier
+            .check_directory(initial_bytes.slice(root_start..root_end), 0)
+            .await?;
+        Ok(verifier)
+    }
+
+    /// Runs the checks that `chunk`, the next streamed bytes, completes.
+    async fn feed<B: AsyncBackend + Sync + Send>(
+        &mut self,
+        backend: &B,
+        chunk: &Bytes,
+    ) -> PmtResult<()> {
+        let chunk_start = self.received;
+        let keep_from = self
+            .pending
+            .peek()
+            .map_or(usize::MAX, |Reverse(check)| check.start);
+        let skip = keep_from.saturating_sub(chunk_start).min(chunk.len());
+        self.window.extend_from_slice(&chunk[skip..]);
+        self.received += chunk.len();
+
+        while let Some(Reverse(check)) = self.pending.pop() {
+            if check.end > self.received {
+                self.pending.push(Reverse(check));
+                break;
+            }
+            // Tiles with identical contents are usually stored once
+            while self
+                .pending
+                .peek()
+                .is_some_and(|Reverse(next)| *next == check)
+            {
+                self.pending.pop();
+            }
+            let window_start = self.received - self.window.len();
+            let bytes = if check.start >= window_start {
+                let range = check.start - window_start..check.end - window_start;
+                Bytes::copy_from_slice(&self.window[range])
+            } else {
+                // A nested leaf directory or tile that has already streamed past
+                backend
+                    .read_exact(check.start, check.end - check.start)
+                    .await?
+            };
+            match check.kind {
+                CheckKind::Leaf(depth) => self.check_directory(bytes, depth).await?,
+                CheckKind::TileSignature => self.check_tile_signature(check.start, &bytes)?,
+            }
+        }
+
+        let window_start = self.received - self.window.len();
+        let keep_from = self
+            .pending
+            .peek()
+            .map_or(self.received, |Reverse(check)| check.start);
+        self.window
+            .advance(keep_from.clamp(window_start, self.received) - window_start);
+        Ok(())
+    }
+
+    /// Checks that the entries of a directory at `depth` point inside their section,
+    /// and queues its leaf directories and, if enabled, its tiles.
+    async fn check_directory(&mut self, bytes: Bytes, depth: usize) -> PmtResult<()> {
+        let directory = decompress(self.header.internal_compression, bytes).await?;
+        let directory = Directory::try_from(directory)?;
+
+        for entry in directory.iter() {
+            let (section_offset, section_length) = if entry.is_leaf() {
+                (self.header.leaf_offset, self.header.leaf_length)
+            } else {
+                (self.header.data_offset, self.header.data_length)
+            };
+            let entry_end = entry
+                .offset
+                .checked_add(u64::from(entry.length))
+                .ok_or(PmtError::InvalidEntry)?;
+            if entry_end > section_length {
+                return Err(PmtError::InvalidEntry);
+            }
+
+            let kind = if entry.is_leaf() {
+                if depth >= MAX_LEAF_DEPTH {
+                    return Err(PmtError::DirectoryTooDeep(MAX_LEAF_DEPTH));
+                }
+                CheckKind::Leaf(depth + 1)
+            } else if self.tile_type.is_some() && entry.length > 0 {
+                CheckKind::TileSignature
+            } else {
+                continue;
+            };
+            let length = match kind {
+                CheckKind::Leaf(_) => entry.length as usize,
+                CheckKind::TileSignature => (entry.length as usize).min(TILE_SIGNATURE_LENGTH),
+            };
+            let start = section_offset
+                .checked_add(entry.offset)
+                .ok_or(PmtError::InvalidEntry)? as usize;
+            let end = start.checked_add(length).ok_or(PmtError::InvalidEntry)?;
+            self.pending
+                .push(Reverse(PendingCheck { start, end, kind }));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the leading bytes of the tile at `offset` match the header's tile type.
+    fn check_tile_signature(&self, offset: usize, bytes: &[u8]) -> PmtResult<()> {
+        let tile_type = ImageInfo::sniff(bytes).map(|info| info.tile_type);
+        if tile_type == self.tile_type {
+            Ok(())
+        } else {
+            Err(PmtError::TileTypeMismatch(offset as u64))
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mmap-async-tokio")]
+mod tests {
+    use super::{copy_archive, CopyOptions};
+    use crate::tests::{RASTER_FILE, VECTOR_FILE};
+    use crate::MmapBackend;
+
+    #[tokio::test]
+    async fn copy_matches_source() {
+        for file in [RASTER_FILE, VECTOR_FILE] {
+            let backend = MmapBackend::try_from(file).await.unwrap();
+            let mut output = Vec::new();
+            let options = CopyOptions {
+                chunk_size: 4096,
+                verify: true,
+                check_tile_types: true,
+            };
+
+            let stats = copy_archive(&backend, &mut output, options).await.unwrap();
+
+            let expected = std::fs::read(file).unwrap();
+            assert_eq!(stats.bytes_copied, expected.len() as u64);
+            assert!(stats.requests > 1);
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "test-utils")]
+    async fn copy_checks_tile_types() {
+        use bytes::Bytes;
+
+        use crate::header::HEADER_SIZE;
+        use crate::test_utils::MemoryBackend;
+        use crate::{Header, PmtError, TileType};
+
+        let mut archive = std::fs::read(RASTER_FILE).unwrap();
+        let header =
+            Header::try_from_bytes(Bytes::copy_from_slice(&archive[..HEADER_SIZE])).unwrap();
+        assert_eq!(header.tile_type, TileType::Png);
+        let options = CopyOptions {
+            check_tile_types: true,
+            ..CopyOptions::default()
+        };
+
+        // Relabel the PNG tiles as JPEG
+        archive[99] = u8::from(TileType::Jpeg);
+        let header =
+            Header::try_from_bytes(Bytes::copy_from_slice(&archive[..HEADER_SIZE])).unwrap();
+        assert_eq!(header.tile_type, TileType::Jpeg);
+        let backend = MemoryBackend::new(archive);
+        let result = copy_archive(&backend, &mut Vec::new(), options).await;
+        assert!(matches!(result, Err(PmtError::TileTypeMismatch(_))));
+
+        let options = CopyOptions {
+            check_tile_types: false,
+            ..options
+        };
+        copy_archive(&backend, &mut Vec::new(), options)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "test-utils")]
+    async fn copy_verifies_leaf_directories() {
+        use crate::header::{HEADER_SIZE, MAX_INITIAL_BYTES};
+        use crate::test_utils::{ArchiveBuilder, MemoryBackend};
+        use crate::{Header, PmtError, TileType};
+
+        let archive = ArchiveBuilder::new(TileType::Png)
+            .zoom_levels(0..=7)
+            .leaf_size(64)
+            .build()
+            .await
+            .unwrap();
+        let options = CopyOptions {
+            chunk_size: 1000,
+            verify: true,
+            check_tile_types: false,
+        };
+        let mut output = Vec::new();
+        let backend = MemoryBackend::new(archive.clone());
+        copy_archive(&backend, &mut output, options).await.unwrap();
+        assert_eq!(output, archive);
+
+        // Break the last leaf directory, which is past the bytes read up front
+        let header = Header::try_from_bytes(archive.slice(..HEADER_SIZE)).unwrap();
+        let leaves_end = (header.leaf_offset + header.leaf_length) as usize;
+        assert!(leaves_end > MAX_INITIAL_BYTES);
+        let mut broken = archive.to_vec();
+        broken[leaves_end - 8..leaves_end].fill(0xff);
+        let backend = MemoryBackend::new(broken);
+        let result = copy_archive(&backend, &mut Vec::new(), options).await;
+        assert!(matches!(result, Err(PmtError::Reading(_))));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "test-utils")]
+    async fn copy_rejects_overflowing_offsets() {
+        use crate::directory::{DirEntry, Directory};
+        use crate::header::HEADER_SIZE;
+        use crate::test_utils::MemoryBackend;
+        use crate::{Compression, Header, PmtError, TileType};
+
+        let archive = |root_offset: Option<u64>, entry: DirEntry| {
+            let mut root = Vec::new();
+            Directory::from_entries(&[entry])
+                .unwrap()
+                .write_to(&mut root)
+                .unwrap();
+            let metadata_offset = HEADER_SIZE as u64 + root.len() as u64;
+            let header = Header::builder(TileType::Png, Compression::None)
+                .internal_compression(Compression::None)
+                .root_directory(root_offset.unwrap_or(HEADER_SIZE as u64), root.len() as u64)
+                .metadata(metadata_offset, 2)
+                .leaf_directories(metadata_offset + 2, 0)
+                .tile_data(metadata_offset + 2, 1)
+                .build();
+            let mut archive = Vec::new();
+            header.write_to(&mut archive).unwrap();
+            archive.extend_from_slice(&root);
+            archive.extend_from_slice(b"{}x");
+            MemoryBackend::new(archive)
+        };
+        let options = CopyOptions::default();
+
+        for entry in [
+            DirEntry::new(0, u64::MAX - 1, 10, 1),
+            DirEntry::new(0, u64::MAX - 1, 10, 0),
+        ] {
+            let result = copy_archive(&archive(None, entry), &mut Vec::new(), options).await;
+            assert!(matches!(result, Err(PmtError::InvalidEntry)), "{entry:?}");
+        }
+
+        let backend = archive(Some(u64::MAX - 1), DirEntry::new(0, 0, 1, 1));
+        let result = copy_archive(&backend, &mut Vec::new(), options).await;
+        assert!(matches!(result, Err(PmtError::InvalidHeader)));
+    }
+}
@@ -1,47 +1,158 @@
 use std::fmt::{Debug, Formatter};
+use std::io::Write;
+use std::ops::Range;
 
 use bytes::{Buf, Bytes};
-use varint_rs::VarintReader;
+use varint_rs::{VarintReader, VarintWriter};
 
-use crate::error::PmtError;
+use crate::error::{PmtError, PmtResult};
+use crate::tile::{TileCoord, TileId};
 
+/// A directory of tile entries.
+///
+/// Entries are stored as parallel columns rather than a `Vec<DirEntry>` to keep cached
+/// directories small: tile IDs and offsets are kept as 32-bit deltas whenever they fit.
 #[derive(Clone)]
 pub struct Directory {
-    entries: Vec<DirEntry>,
+    tile_ids: PackedColumn,
+    offsets: PackedColumn,
+    lengths: Box<[u32]>,
+    run_lengths: Box<[u32]>,
 }
 
 impl Debug for Directory {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("Directory [entries: {}]", self.entries.len()))
+        f.write_fmt(format_args!("Directory [entries: {}]", self.len()))
     }
 }
 
 impl Directory {
+    /// Create a directory from entries sorted by ascending, unique tile ID.
+    pub fn from_entries(entries: &[DirEntry]) -> PmtResult<Self> {
+        if entries.windows(2).any(|w| w[0].tile_id >= w[1].tile_id) {
+            return Err(PmtError::InvalidEntry);
+        }
+        Ok(Self {
+            tile_ids: entries.iter().map(|e| e.tile_id).collect(),
+            offsets: entries.iter().map(|e| e.offset).collect(),
+            lengths: entries.iter().map(|e| e.length).collect(),
+            run_lengths: entries.iter().map(|e| e.run_length).collect(),
+        })
+    }
+
+    /// Serialize the directory in the uncompressed `PMTiles` v3 directory encoding.
+    ///
+    /// The output can be read back with [`Directory::try_from`] once decompressed.
+    /// Callers are responsible for applying the archive's internal compression.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> PmtResult<()> {
+        writer.write_usize_varint(self.len())?;
+
+        // Write tile IDs
+        let mut last_tile_id = 0;
+        for entry in self.iter() {
+            writer.write_u64_varint(entry.tile_id - last_tile_id)?;
+            last_tile_id = entry.tile_id;
+        }
+
+        // Write Run Lengths
+        for run_length in &self.run_lengths {
+            writer.write_u32_varint(*run_length)?;
+        }
+
+        // Write Lengths
+        for length in &self.lengths {
+            writer.write_u32_varint(*length)?;
+        }
+
+        // Write Offsets
+        let mut last_entry: Option<DirEntry> = None;
+        for entry in self.iter() {
+            let offset = match last_entry {
+                Some(e) if entry.offset == e.offset + u64::from(e.length) => 0,
+                _ => entry.offset + 1,
+            };
+            writer.write_u64_varint(offset)?;
+            last_entry = Some(entry);
+        }
+
+        Ok(())
+    }
+
+    /// Number of entries in this directory.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lengths.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lengths.is_empty()
+    }
+
+    /// Get the entry at `index`, in tile ID order.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<DirEntry> {
+        (index < self.len()).then(|| self.entry(index))
+    }
+
+    /// Iterate over the directory entries in tile ID order.
+    #[must_use]
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = DirEntry> + '_ {
+        (0..self.len()).map(|idx| self.entry(idx))
+    }
+
+    /// Assemble the entry at `index`, which must be in bounds.
+    fn entry(&self, index: usize) -> DirEntry {
+        DirEntry {
+            tile_id: self.tile_ids.get(index),
+            offset: self.offsets.get(index),
+            length: self.lengths[index],
+            run_length: self.run_lengths[index],
+        }
+    }
+
     /// Find the directory entry for a given tile ID.
+    ///
+    /// Entries are not stored individually, so the entry is assembled and returned by value.
     #[must_use]
-    pub fn find_tile_id(&self, tile_id: u64) -> Option<&DirEntry> {
-        match self.entries.binary_search_by(|e| e.tile_id.cmp(&tile_id)) {
-            Ok(idx) => self.entries.get(idx),
-            Err(next_id) => {
-                // Adapted from javascript code at
-                // https://github.com/protomaps/PMTiles/blob/9c7f298fb42290354b8ed0a9b2f50e5c0d270c40/js/index.ts#L210
-                if next_id > 0 {
-                    let previous_tile = self.entries.get(next_id - 1)?;
-                    if previous_tile.is_leaf()
-                        || tile_id - previous_tile.tile_id < u64::from(previous_tile.run_length)
-                    {
-                        return Some(previous_tile);
-                    }
-                }
-                None
+    pub fn find_tile_id(&self, tile_id: u64) -> Option<DirEntry> {
+        self.find_index(tile_id).map(|idx| self.entry(idx))
+    }
+
+    /// Index of the entry covering `tile_id`, if any.
+    fn find_index(&self, tile_id: u64) -> Option<usize> {
+        // Index of the first entry with a tile ID greater than the one we are looking for
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.tile_ids.get(mid) <= tile_id {
+                low = mid + 1;
+            } else {
+                high = mid;
             }
         }
+
+        // Adapted from javascript code at
+        // https://github.com/protomaps/PMTiles/blob/9c7f298fb42290354b8ed0a9b2f50e5c0d270c40/js/index.ts#L210
+        let idx = low.checked_sub(1)?;
+        let previous_tile = self.get(idx)?;
+        if previous_tile.tile_id == tile_id
+            || previous_tile.is_leaf()
+            || tile_id - previous_tile.tile_id < u64::from(previous_tile.run_length)
+        {
+            Some(idx)
+        } else {
+            None
+        }
     }
 
     /// Get an estimated byte size of the directory object. Use this for cache eviction.
     #[must_use]
     pub fn get_approx_byte_size(&self) -> usize {
-        self.entries.capacity() * size_of::<DirEntry>()
+        self.tile_ids.byte_size()
+            + self.offsets.byte_size()
+            + size_of_val(&*self.lengths)
+            + size_of_val(&*self.run_lengths)
     }
 }
 
@@ -52,43 +163,92 @@ impl TryFrom<Bytes> for Directory {
         let mut buffer = buffer.reader();
         let n_entries = buffer.read_usize_varint()?;
 
-        let mut entries = vec![DirEntry::default(); n_entries];
-
         // Read tile IDs
+        let mut tile_ids = Vec::with_capacity(n_entries);
         let mut next_tile_id = 0;
-        for entry in &mut entries {
+        for _ in 0..n_entries {
             next_tile_id += buffer.read_u64_varint()?;
-            entry.tile_id = next_tile_id;
+            tile_ids.push(next_tile_id);
         }
 
         // Read Run Lengths
-        for entry in &mut entries {
-            entry.run_length = buffer.read_u32_varint()?;
-        }
+        let run_lengths = (0..n_entries)
+            .map(|_| buffer.read_u32_varint())
+            .collect::<Result<Box<[u32]>, _>>()?;
 
         // Read Lengths
-        for entry in &mut entries {
-            entry.length = buffer.read_u32_varint()?;
-        }
+        let lengths = (0..n_entries)
+            .map(|_| buffer.read_u32_varint())
+            .collect::<Result<Box<[u32]>, _>>()?;
 
         // Read Offsets
-        let mut last_entry: Option<&DirEntry> = None;
-        for entry in &mut entries {
+        let mut offsets = Vec::with_capacity(n_entries);
+        let mut last_entry: Option<(u64, u32)> = None;
+        for length in &lengths {
             let offset = buffer.read_u64_varint()?;
-            entry.offset = if offset == 0 {
-                let e = last_entry.ok_or(PmtError::InvalidEntry)?;
-                e.offset + u64::from(e.length)
+            let offset = if offset == 0 {
+                let (last_offset, last_length) = last_entry.ok_or(PmtError::InvalidEntry)?;
+                last_offset + u64::from(last_length)
             } else {
                 offset - 1
             };
-            last_entry = Some(entry);
+            offsets.push(offset);
+            last_entry = Some((offset, *length));
+        }
+
+        Ok(Directory {
+            tile_ids: PackedColumn::from(tile_ids),
+            offsets: PackedColumn::from(offsets),
+            lengths,
+            run_lengths,
+        })
+    }
+}
+
+/// A column of `u64` values, stored as 32-bit deltas from the smallest value when the range allows.
+#[derive(Clone)]
+enum PackedColumn {
+    Narrow { base: u64, deltas: Box<[u32]> },
+    Wide(Box<[u64]>),
+}
+
+impl PackedColumn {
+    fn get(&self, index: usize) -> u64 {
+        match self {
+            Self::Narrow { base, deltas } => base + u64::from(deltas[index]),
+            Self::Wide(values) => values[index],
         }
+    }
+
+    fn byte_size(&self) -> usize {
+        match self {
+            Self::Narrow { deltas, .. } => size_of_val(&**deltas),
+            Self::Wide(values) => size_of_val(&**values),
+        }
+    }
+}
+
+impl From<Vec<u64>> for PackedColumn {
+    fn from(values: Vec<u64>) -> Self {
+        let base = values.iter().copied().min().unwrap_or_default();
+        let max = values.iter().copied().max().unwrap_or_default();
+        if u32::try_from(max - base).is_ok() {
+            #[allow(clippy::cast_possible_truncation)]
+            let deltas = values.iter().map(|v| (v - base) as u32).collect();
+            Self::Narrow { base, deltas }
+        } else {
+            Self::Wide(values.into_boxed_slice())
+        }
+    }
+}
 
-        Ok(Directory { entries })
+impl FromIterator<u64> for PackedColumn {
+    fn from_iter<T: IntoIterator<Item = u64>>(iter: T) -> Self {
+        Self::from(iter.into_iter().collect::<Vec<_>>())
     }
 }
 
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
 pub struct DirEntry {
     pub(crate) tile_id: u64,
     pub(crate) offset: u64,
@@ -97,20 +257,98 @@ pub struct DirEntry {
 }
 
 impl DirEntry {
-    pub(crate) fn is_leaf(&self) -> bool {
+    /// Create a new directory entry.
+    ///
+    /// A `run_length` of `0` makes this a pointer to a leaf directory,
+    /// otherwise the entry covers `run_length` consecutive tile IDs sharing the same data.
+    #[must_use]
+    pub fn new(tile_id: u64, offset: u64, length: u32, run_length: u32) -> Self {
+        Self {
+            tile_id,
+            offset,
+            length,
+            run_length,
+        }
+    }
+
+    /// The first tile ID covered by this entry.
+    #[must_use]
+    pub fn tile_id(&self) -> u64 {
+        self.tile_id
+    }
+
+    /// Offset of the tile data or leaf directory, relative to the start of its section.
+    #[must_use]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Length of the tile data or leaf directory in bytes.
+    #[must_use]
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// Number of consecutive tile IDs sharing this entry's data, `0` for leaf directory pointers.
+    #[must_use]
+    pub fn run_length(&self) -> u32 {
+        self.run_length
+    }
+
+    /// Whether this entry points to a leaf directory rather than tile data.
+    #[must_use]
+    pub fn is_leaf(&self) -> bool {
         self.run_length == 0
     }
+
+    /// Tile IDs covered by this entry's run, in ascending order. Empty for leaf directory pointers.
+    pub fn iter_tile_ids(&self) -> impl Iterator<Item = TileId> {
+        self.tile_id_range().map_while(|id| TileId::new(id).ok())
+    }
+
+    /// Coordinates of the tiles covered by this entry's run, in tile ID order.
+    /// Empty for leaf directory pointers.
+    #[must_use]
+    pub fn iter_coords(&self) -> DirEntryCoordsIter {
+        DirEntryCoordsIter {
+            tile_ids: self.tile_id_range(),
+        }
+    }
+
+    fn tile_id_range(&self) -> Range<u64> {
+        self.tile_id..self.tile_id.saturating_add(u64::from(self.run_length))
+    }
+}
+
+/// Iterator over the tile coordinates of a [`DirEntry`], see [`DirEntry::iter_coords`].
+#[derive(Debug, Clone)]
+pub struct DirEntryCoordsIter {
+    tile_ids: Range<u64>,
+}
+
+impl Iterator for DirEntryCoordsIter {
+    type Item = TileCoord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tile_id = TileId::new(self.tile_ids.next()?).ok()?;
+        Some(tile_id.into())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.tile_ids.size_hint().1)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::io::{BufReader, Read, Write};
 
-    use bytes::BytesMut;
+    use bytes::{Bytes, BytesMut};
 
-    use super::Directory;
+    use super::{DirEntry, Directory};
     use crate::header::HEADER_SIZE;
     use crate::tests::RASTER_FILE;
+    use crate::tile::{TileCoord, TileId};
     use crate::Header;
 
     #[test]
@@ -133,16 +371,150 @@ mod tests {
 
         let directory = Directory::try_from(decompressed.freeze()).unwrap();
 
-        assert_eq!(directory.entries.len(), 84);
+        assert_eq!(directory.len(), 84);
         // Note: this is not true for all tiles, just the first few...
         for nth in 0..10 {
-            assert_eq!(directory.entries[nth].tile_id, nth as u64);
+            assert_eq!(directory.get(nth).unwrap().tile_id, nth as u64);
         }
 
         // ...it breaks pattern on the 59th tile
-        assert_eq!(directory.entries[58].tile_id, 58);
-        assert_eq!(directory.entries[58].run_length, 2);
-        assert_eq!(directory.entries[58].offset, 422_070);
-        assert_eq!(directory.entries[58].length, 850);
+        let entry = directory.get(58).unwrap();
+        assert_eq!(entry.tile_id, 58);
+        assert_eq!(entry.run_length, 2);
+        assert_eq!(entry.offset, 422_070);
+        assert_eq!(entry.length, 850);
+    }
+
+    #[test]
+    fn write_directory_round_trip() {
+        let test_file = std::fs::File::open(RASTER_FILE).unwrap();
+        let mut reader = BufReader::new(test_file);
+
+        let mut header_bytes = BytesMut::zeroed(HEADER_SIZE);
+        reader.read_exact(header_bytes.as_mut()).unwrap();
+
+        let header = Header::try_from_bytes(header_bytes.freeze()).unwrap();
+        let mut directory_bytes = BytesMut::zeroed(usize::try_from(header.root_length).unwrap());
+        reader.read_exact(directory_bytes.as_mut()).unwrap();
+
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(&directory_bytes[..])
+            .read_to_end(&mut decompressed)
+            .unwrap();
+
+        let directory = Directory::try_from(Bytes::from(decompressed.clone())).unwrap();
+        let mut written = Vec::new();
+        directory.write_to(&mut written).unwrap();
+        assert_eq!(written, decompressed);
+
+        let entries: Vec<DirEntry> = directory.iter().collect();
+        let rebuilt = Directory::from_entries(&entries).unwrap();
+        assert_eq!(rebuilt.len(), 84);
+        assert_eq!(rebuilt.find_tile_id(58), directory.find_tile_id(58));
+    }
+
+    #[test]
+    fn from_entries_requires_sorted_ids() {
+        let sorted = vec![DirEntry::new(0, 0, 10, 1), DirEntry::new(1, 10, 10, 1)];
+        assert!(Directory::from_entries(&sorted).is_ok());
+
+        let unsorted = vec![DirEntry::new(1, 0, 10, 1), DirEntry::new(0, 10, 10, 1)];
+        assert!(Directory::from_entries(&unsorted).is_err());
+
+        let duplicate = vec![DirEntry::new(1, 0, 10, 1), DirEntry::new(1, 10, 10, 1)];
+        assert!(Directory::from_entries(&duplicate).is_err());
+    }
+
+    #[test]
+    fn write_offsets() {
+        let directory = Directory::from_entries(&[
+            DirEntry::new(0, 0, 10, 1),
+            DirEntry::new(1, 10, 5, 2),
+            DirEntry::new(5, 0, 10, 1),
+        ])
+        .unwrap();
+        let mut written = Vec::new();
+        directory.write_to(&mut written).unwrap();
+        // count, tile id deltas, run lengths, lengths, offsets (0 = contiguous with previous)
+        assert_eq!(written, [3, 0, 1, 4, 1, 2, 1, 10, 5, 10, 1, 0, 1]);
+
+        let decoded = Directory::try_from(Bytes::from(written)).unwrap();
+        assert_eq!(
+            decoded.iter().collect::<Vec<_>>(),
+            directory.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn find_tile_id() {
+        let directory = Directory::from_entries(&[
+            DirEntry::new(0, 0, 10, 1),
+            DirEntry::new(2, 10, 10, 3),
+            DirEntry::new(10, 0, 100, 0),
+            DirEntry::new(20, 20, 10, 1),
+        ])
+        .unwrap();
+
+        assert_eq!(directory.find_tile_id(0).unwrap().tile_id, 0);
+        assert!(directory.find_tile_id(1).is_none());
+        assert_eq!(directory.find_tile_id(4).unwrap().tile_id, 2);
+        assert!(directory.find_tile_id(5).is_none());
+        // leaf pointers cover everything up to the next entry
+        assert_eq!(directory.find_tile_id(15).unwrap().tile_id, 10);
+        assert_eq!(directory.find_tile_id(20).unwrap().tile_id, 20);
+        assert!(directory.find_tile_id(21).is_none());
+        assert!(Directory::from_entries(&[])
+            .unwrap()
+            .find_tile_id(0)
+            .is_none());
+    }
+
+    #[test]
+    fn packed_columns() {
+        let narrow = Directory::from_entries(&[
+            DirEntry::new(1 << 40, 1 << 35, 10, 1),
+            DirEntry::new((1 << 40) + 1, (1 << 35) + 10, 10, 1),
+        ])
+        .unwrap();
+        assert_eq!(narrow.get_approx_byte_size(), 2 * 16);
+        assert_eq!(narrow.get(1).unwrap().offset, (1 << 35) + 10);
+
+        let wide = Directory::from_entries(&[
+            DirEntry::new(0, 0, 10, 1),
+            DirEntry::new(1 << 40, 1 << 35, 10, 1),
+        ])
+        .unwrap();
+        assert_eq!(wide.get_approx_byte_size(), 2 * 24);
+        assert_eq!(wide.get(1).unwrap(), DirEntry::new(1 << 40, 1 << 35, 10, 1));
+        assert!(wide.get(2).is_none());
+    }
+
+    #[test]
+    fn dir_entry_accessors() {
+        let entry = DirEntry::new(58, 422_070, 850, 2);
+        assert_eq!(entry.tile_id(), 58);
+        assert_eq!(entry.offset(), 422_070);
+        assert_eq!(entry.length(), 850);
+        assert_eq!(entry.run_length(), 2);
+        assert!(!entry.is_leaf());
+        assert!(DirEntry::new(0, 0, 100, 0).is_leaf());
+    }
+
+    #[test]
+    fn dir_entry_coords() {
+        // Tile IDs 4 and 5 are the last tile of zoom 1 and the first of zoom 2
+        let entry = DirEntry::new(4, 0, 100, 2);
+        let coords: Vec<_> = entry.iter_coords().collect();
+        assert_eq!(
+            coords,
+            [
+                TileCoord::new(1, 1, 0).unwrap(),
+                TileCoord::new(2, 0, 0).unwrap()
+            ]
+        );
+        let ids: Vec<_> = entry.iter_tile_ids().map(TileId::value).collect();
+        assert_eq!(ids, [4, 5]);
+
+        assert_eq!(DirEntry::new(4, 0, 100, 0).iter_coords().count(), 0);
     }
 }
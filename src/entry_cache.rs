@@ -0,0 +1,96 @@
+use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroUsize;
+
+use crate::cache::DirCacheResult;
+use crate::directory::DirEntry;
+
+/// A least-recently-used map of resolved tile lookups, including tiles found to be missing,
+/// so repeated requests for the same tiles skip the directory search.
+pub(crate) struct EntryCache {
+    capacity: NonZeroUsize,
+    entries: HashMap<u64, (Option<DirEntry>, u64)>,
+    /// Tile IDs by the tick they were last used at, oldest first.
+    recency: BTreeMap<u64, u64>,
+    tick: u64,
+}
+
+impl EntryCache {
+    pub(crate) fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// The cached lookup result for `tile_id`, marking it as recently used.
+    pub(crate) fn get(&mut self, tile_id: u64) -> DirCacheResult {
+        let tick = self.next_tick();
+        let Some((entry, used)) = self.entries.get_mut(&tile_id) else {
+            return DirCacheResult::NotCached;
+        };
+        self.recency.remove(used);
+        self.recency.insert(tick, tile_id);
+        *used = tick;
+        (*entry).into()
+    }
+
+    pub(crate) fn insert(&mut self, tile_id: u64, entry: Option<DirEntry>) {
+        let tick = self.next_tick();
+        if let Some((_, used)) = self.entries.insert(tile_id, (entry, tick)) {
+            self.recency.remove(&used);
+        } else if self.entries.len() > self.capacity.get() {
+            if let Some((_, oldest)) = self.recency.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.recency.insert(tick, tile_id);
+    }
+
+    /// The cached tile IDs, least recently used first.
+    pub(crate) fn tile_ids(&self) -> Vec<u64> {
+        self.recency.values().copied().collect()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use super::EntryCache;
+    use crate::cache::DirCacheResult;
+    use crate::directory::DirEntry;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = EntryCache::new(NonZeroUsize::new(2).unwrap());
+        let entry = DirEntry::new(1, 0, 10, 1);
+        cache.insert(1, Some(entry));
+        cache.insert(2, None);
+        assert!(matches!(cache.get(1), DirCacheResult::Found(e) if e == entry));
+
+        cache.insert(3, None);
+        assert!(matches!(cache.get(2), DirCacheResult::NotCached));
+        assert!(matches!(cache.get(1), DirCacheResult::Found(e) if e == entry));
+        assert!(matches!(cache.get(3), DirCacheResult::NotFound));
+
+        cache.insert(3, Some(entry));
+        assert_eq!(cache.tile_ids(), [1, 3]);
+        assert_eq!(cache.entries.len(), 2);
+        assert_eq!(cache.recency.len(), 2);
+
+        cache.clear();
+        assert!(matches!(cache.get(1), DirCacheResult::NotCached));
+    }
+}
@@ -28,8 +28,28 @@ pub enum PmtError {
     InvalidMetadataUtf8Encoding(#[from] FromUtf8Error),
     #[error("Invalid tile type")]
     InvalidTileType,
+    #[error("Invalid tile coordinates")]
+    InvalidTileCoord,
+    #[error("Invalid bounding box")]
+    InvalidBoundingBox,
     #[error("IO Error {0}")]
     Reading(#[from] std::io::Error),
+    #[error(transparent)]
+    Backend(Box<BackendError>),
+    #[error("Operation timed out")]
+    Timeout,
+    #[error("The archive has changed since it was first read")]
+    ArchiveChanged,
+    #[error(
+        "Archive is truncated: the header declares {0} bytes, but only {1} bytes are available"
+    )]
+    ArchiveTruncated(u64, u64),
+    #[error("No enabled backend can open {0}")]
+    UnsupportedLocation(String),
+    #[error("Leaf directories are nested more than {0} levels deep")]
+    DirectoryTooDeep(usize),
+    #[error("The tile at offset {0} does not match the archive's tile type")]
+    TileTypeMismatch(u64),
     #[cfg(feature = "mmap-async-tokio")]
     #[error("Unable to open mmap file")]
     UnableToOpenMmapFile,
@@ -60,3 +80,137 @@ pub enum PmtError {
         #[from] aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>,
     ),
 }
+
+/// A failed read from an [`AsyncBackend`](crate::async_reader::AsyncBackend),
+/// with the resource and byte range that were requested.
+#[derive(Debug, Error)]
+#[error(
+    "Reading {length} bytes at offset {offset} from {} failed: {source}",
+    .resource.as_deref().unwrap_or("backend")
+)]
+pub struct BackendError {
+    /// The archive that was read, as described by the backend, e.g. a URL or an S3 key.
+    pub resource: Option<String>,
+    pub offset: usize,
+    pub length: usize,
+    #[source]
+    pub source: PmtError,
+}
+
+impl BackendError {
+    /// Whether retrying the same read may succeed, e.g. after a timeout, a dropped connection,
+    /// throttling or a server error.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        is_retryable(&self.source)
+    }
+
+    /// Whether the read failed because the archive does not exist.
+    #[must_use]
+    pub fn is_not_found(&self) -> bool {
+        is_not_found(&self.source)
+    }
+}
+
+#[cfg(any(
+    feature = "http-async",
+    feature = "__async-s3",
+    feature = "__async-aws-s3"
+))]
+fn is_retryable_status(status: u16) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+fn is_retryable(err: &PmtError) -> bool {
+    use std::io::ErrorKind;
+
+    match err {
+        PmtError::Reading(e) => matches!(
+            e.kind(),
+            ErrorKind::TimedOut
+                | ErrorKind::Interrupted
+                | ErrorKind::WouldBlock
+                | ErrorKind::ConnectionRefused
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::BrokenPipe
+        ),
+        PmtError::Backend(e) => e.is_retryable(),
+        PmtError::Timeout => true,
+        #[cfg(feature = "http-async")]
+        PmtError::Http(e) => {
+            e.is_timeout()
+                || e.is_connect()
+                || e.status()
+                    .is_some_and(|status| is_retryable_status(status.as_u16()))
+        }
+        #[cfg(feature = "__async-s3")]
+        PmtError::S3(s3::error::S3Error::HttpFailWithBody(status, _)) => {
+            is_retryable_status(*status)
+        }
+        #[cfg(feature = "__async-aws-s3")]
+        PmtError::AwsS3Request(e) => {
+            use aws_sdk_s3::error::SdkError;
+            match e {
+                SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) => true,
+                SdkError::ResponseError(_) | SdkError::ServiceError(_) => e
+                    .raw_response()
+                    .is_some_and(|r| is_retryable_status(r.status().as_u16())),
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+fn is_not_found(err: &PmtError) -> bool {
+    match err {
+        PmtError::Reading(e) => e.kind() == std::io::ErrorKind::NotFound,
+        PmtError::Backend(e) => e.is_not_found(),
+        #[cfg(feature = "http-async")]
+        PmtError::Http(e) => e.status() == Some(reqwest::StatusCode::NOT_FOUND),
+        #[cfg(feature = "__async-s3")]
+        PmtError::S3(s3::error::S3Error::HttpFailWithBody(status, _)) => *status == 404,
+        #[cfg(feature = "__async-aws-s3")]
+        PmtError::AwsS3Request(e) => {
+            e.as_service_error()
+                .is_some_and(aws_sdk_s3::operation::get_object::GetObjectError::is_no_such_key)
+                || e.raw_response().is_some_and(|r| r.status().as_u16() == 404)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{BackendError, PmtError};
+
+    fn backend_error(kind: io::ErrorKind) -> BackendError {
+        BackendError {
+            resource: Some("test.pmtiles".to_string()),
+            offset: 127,
+            length: 16,
+            source: PmtError::Reading(io::Error::from(kind)),
+        }
+    }
+
+    #[test]
+    fn backend_error_kinds() {
+        let err = backend_error(io::ErrorKind::TimedOut);
+        assert!(err.is_retryable());
+        assert!(!err.is_not_found());
+
+        let err = backend_error(io::ErrorKind::NotFound);
+        assert!(!err.is_retryable());
+        assert!(err.is_not_found());
+
+        let err = backend_error(io::ErrorKind::UnexpectedEof);
+        assert!(!err.is_retryable());
+        assert!(!err.is_not_found());
+        assert!(err
+            .to_string()
+            .starts_with("Reading 16 bytes at offset 127 from test.pmtiles failed"));
+    }
+}
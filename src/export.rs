@@ -0,0 +1,214 @@
+use std::path::{Path, PathBuf};
+
+use crate::async_reader::{AccessPattern, AsyncBackend, AsyncPmTilesReader, MAX_LEAF_DEPTH};
+use crate::cache::DirectoryCache;
+use crate::codec::decompress;
+use crate::directory::DirEntry;
+use crate::error::{PmtError, PmtResult};
+use crate::header::TileType;
+
+/// How the rows of exported tiles are numbered in file names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NamingScheme {
+    /// `z/x/y.ext` with row 0 at the top, as used by XYZ tile URLs.
+    #[default]
+    Xyz,
+    /// `z/x/y.ext` with row 0 at the bottom, as used by TMS.
+    Tms,
+}
+
+/// File extension for tiles of the given type.
+#[must_use]
+pub fn extension(tile_type: TileType) -> &'static str {
+    match tile_type {
+        TileType::Mvt => "mvt",
+        TileType::Png => "png",
+        TileType::Jpeg => "jpg",
+        TileType::Webp => "webp",
+        TileType::Avif => "avif",
+        TileType::Unknown => "bin",
+    }
+}
+
+/// Writes every tile of the archive to a `z/x/y.ext` file below `path`,
+/// with the extension matching the archive's tile type.
+///
+/// Tiles are written as stored, i.e. still compressed with the archive's tile compression,
+/// unless `decompress_tiles` is set. Returns the number of files written.
+pub async fn to_directory<B, C>(
+    reader: &AsyncPmTilesReader<B, C>,
+    path: impl AsRef<Path>,
+    scheme: NamingScheme,
+    decompress_tiles: bool,
+) -> PmtResult<u64>
+where
+    B: AsyncBackend + Sync + Send,
+    C: DirectoryCache + Sync + Send,
+{
+    reader.hint(AccessPattern::Sequential);
+    let written = write_tiles(reader, path.as_ref(), scheme, decompress_tiles).await;
+    reader.hint(AccessPattern::Random);
+    written
+}
+
+/// Walks the directories depth-first, so tiles are read in tile ID order,
+/// and only the leaf directories on the current path are held in memory.
+async fn write_tiles<B, C>(
+    reader: &AsyncPmTilesReader<B, C>,
+    path: &Path,
+    scheme: NamingScheme,
+    decompress_tiles: bool,
+) -> PmtResult<u64>
+where
+    B: AsyncBackend + Sync + Send,
+    C: DirectoryCache + Sync + Send,
+{
+    let mut writer = TileWriter {
+        path,
+        scheme,
+        decompress_tiles,
+        last_dir: None,
+        written: 0,
+    };
+
+    for entry in reader.root_directory().iter() {
+        if !entry.is_leaf() {
+            writer.write(reader, &entry).await?;
+            continue;
+        }
+        let mut stack = vec![(reader.read_leaf_directory(&entry).await?, 0)];
+        while let Some((dir, index)) = stack.last_mut() {
+            let Some(entry) = dir.get(*index) else {
+                stack.pop();
+                continue;
+            };
+            *index += 1;
+
+            if !entry.is_leaf() {
+                writer.write(reader, &entry).await?;
+            } else if stack.len() >= MAX_LEAF_DEPTH {
+                return Err(PmtError::DirectoryTooDeep(MAX_LEAF_DEPTH));
+            } else {
+                stack.push((reader.read_leaf_directory(&entry).await?, 0));
+            }
+        }
+    }
+
+    Ok(writer.written)
+}
+
+struct TileWriter<'a> {
+    path: &'a Path,
+    scheme: NamingScheme,
+    decompress_tiles: bool,
+    /// The last `z/x` directory created, as consecutive tiles usually share it.
+    last_dir: Option<PathBuf>,
+    written: u64,
+}
+
+impl TileWriter<'_> {
+    /// Writes the tile data of `entry` to the file of each tile in its run.
+    async fn write<B, C>(
+        &mut self,
+        reader: &AsyncPmTilesReader<B, C>,
+        entry: &DirEntry,
+    ) -> PmtResult<()>
+    where
+        B: AsyncBackend + Sync + Send,
+        C: DirectoryCache + Sync + Send,
+    {
+        let header = reader.get_header();
+        let extension = extension(header.tile_type);
+        let mut data = reader.read_tile_data(entry).await?;
+        if self.decompress_tiles {
+            data = decompress(header.tile_compression, data).await?;
+        }
+
+        for coord in entry.iter_coords() {
+            let y = match self.scheme {
+                NamingScheme::Xyz => coord.y(),
+                NamingScheme::Tms => coord.tms_y(),
+            };
+            let dir = self
+                .path
+                .join(coord.z().to_string())
+                .join(coord.x().to_string());
+            if self.last_dir.as_ref() != Some(&dir) {
+                tokio::fs::create_dir_all(&dir).await?;
+            }
+            tokio::fs::write(dir.join(format!("{y}.{extension}")), &data).await?;
+            self.last_dir = Some(dir);
+            self.written += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mmap-async-tokio")]
+mod tests {
+    use super::{to_directory, NamingScheme};
+    use crate::async_reader::AsyncPmTilesReader;
+    use crate::tests::{RASTER_FILE, VECTOR_FILE};
+    use crate::MmapBackend;
+
+    #[tokio::test]
+    async fn export_tiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let written = to_directory(&tiles, dir.join("xyz"), NamingScheme::Xyz, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            written,
+            tiles.get_header().n_addressed_tiles().unwrap().get()
+        );
+        let tile = tiles.get_tile(3, 2, 1).await.unwrap().unwrap();
+        assert_eq!(std::fs::read(dir.join("xyz/3/2/1.png")).unwrap(), tile);
+
+        to_directory(&tiles, dir.join("tms"), NamingScheme::Tms, false)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(dir.join("tms/3/2/6.png")).unwrap(), tile);
+
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        to_directory(&tiles, dir.join("mvt"), NamingScheme::Xyz, true)
+            .await
+            .unwrap();
+        let tile = std::fs::read(dir.join("mvt/0/0/0.mvt")).unwrap();
+        // Decompressed vector tiles start with a layer field tag
+        assert_eq!(tile[0], 0x1A);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "test-utils")]
+    async fn export_tiles_in_leaves() {
+        use crate::test_utils::{ArchiveBuilder, MemoryBackend};
+        use crate::TileType;
+
+        let archive = ArchiveBuilder::new(TileType::Png)
+            .zoom_levels(0..=3)
+            .leaf_size(4)
+            .build()
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(MemoryBackend::new(archive))
+            .await
+            .unwrap();
+        assert!(tiles.leaf_count() > 0);
+
+        let dir = tempfile::tempdir().unwrap();
+        let written = to_directory(&tiles, dir.path(), NamingScheme::Xyz, false)
+            .await
+            .unwrap();
+        assert_eq!(written, 85);
+        for (z, x, y) in [(0, 0, 0), (2, 3, 1), (3, 5, 7)] {
+            let tile = std::fs::read(dir.path().join(format!("{z}/{x}/{y}.png"))).unwrap();
+            assert_eq!(tile, format!("{z}/{x}/{y}").as_bytes());
+        }
+    }
+}
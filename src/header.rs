@@ -1,16 +1,20 @@
+use std::io::Write;
 use std::num::NonZeroU64;
+use std::ops::Range;
 use std::panic::catch_unwind;
 
 use bytes::{Buf, Bytes};
 
 use crate::error::{PmtError, PmtResult};
+use crate::tile::TileCoord;
 
-#[cfg(feature = "__async")]
-pub(crate) const MAX_INITIAL_BYTES: usize = 16_384;
-#[cfg(any(test, feature = "__async"))]
-pub(crate) const HEADER_SIZE: usize = 127;
+/// Number of bytes at the start of an archive that hold its header and root directory.
+/// Reading this many bytes is enough to open an archive.
+pub const MAX_INITIAL_BYTES: usize = 16_384;
+/// Size of a `PMTiles` v3 header in bytes.
+pub const HEADER_SIZE: usize = 127;
 
-#[allow(dead_code)]
+#[derive(Debug, Clone)]
 pub struct Header {
     pub(crate) version: u8,
     pub(crate) root_offset: u64,
@@ -30,13 +34,40 @@ pub struct Header {
     pub tile_type: TileType,
     pub min_zoom: u8,
     pub max_zoom: u8,
-    pub min_longitude: f32,
-    pub min_latitude: f32,
-    pub max_longitude: f32,
-    pub max_latitude: f32,
+    pub min_longitude: f64,
+    pub min_latitude: f64,
+    pub max_longitude: f64,
+    pub max_latitude: f64,
     pub center_zoom: u8,
-    pub center_longitude: f32,
-    pub center_latitude: f32,
+    pub center_longitude: f64,
+    pub center_latitude: f64,
+}
+
+/// Byte ranges of the sections of an archive, see [`Header::layout`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveLayout {
+    pub header: Range<u64>,
+    pub root_directory: Range<u64>,
+    pub metadata: Range<u64>,
+    pub leaf_directories: Range<u64>,
+    pub tile_data: Range<u64>,
+}
+
+impl ArchiveLayout {
+    /// The end of the last section, i.e. the archive's expected size.
+    #[must_use]
+    pub fn end(&self) -> u64 {
+        [
+            &self.header,
+            &self.root_directory,
+            &self.metadata,
+            &self.leaf_directories,
+            &self.tile_data,
+        ]
+        .into_iter()
+        .map(|range| range.end)
+        .fold(0, u64::max)
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -74,6 +105,18 @@ impl TryInto<Compression> for u8 {
     }
 }
 
+impl From<Compression> for u8 {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::Unknown => 0,
+            Compression::None => 1,
+            Compression::Gzip => 2,
+            Compression::Brotli => 3,
+            Compression::Zstd => 4,
+        }
+    }
+}
+
 #[cfg(feature = "tilejson")]
 impl Header {
     #[must_use]
@@ -90,30 +133,33 @@ impl Header {
     #[must_use]
     pub fn get_bounds(&self) -> tilejson::Bounds {
         tilejson::Bounds::new(
-            f64::from(self.min_longitude),
-            f64::from(self.min_latitude),
-            f64::from(self.max_longitude),
-            f64::from(self.max_latitude),
+            self.min_longitude,
+            self.min_latitude,
+            self.max_longitude,
+            self.max_latitude,
         )
     }
 
     #[must_use]
     pub fn get_center(&self) -> tilejson::Center {
         tilejson::Center::new(
-            f64::from(self.center_longitude),
-            f64::from(self.center_latitude),
+            self.center_longitude,
+            self.center_latitude,
             self.center_zoom,
         )
     }
 }
 
+/// Format of the tiles in an archive. More formats may be added to the spec over time.
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[non_exhaustive]
 pub enum TileType {
     Unknown,
     Mvt,
     Png,
     Jpeg,
     Webp,
+    Avif,
 }
 
 impl TileType {
@@ -124,11 +170,25 @@ impl TileType {
             TileType::Png => "image/png",
             TileType::Webp => "image/webp",
             TileType::Jpeg => "image/jpeg",
+            TileType::Avif => "image/avif",
             TileType::Unknown => "application/octet-stream",
         }
     }
 }
 
+impl From<TileType> for u8 {
+    fn from(tile_type: TileType) -> Self {
+        match tile_type {
+            TileType::Unknown => 0,
+            TileType::Mvt => 1,
+            TileType::Png => 2,
+            TileType::Jpeg => 3,
+            TileType::Webp => 4,
+            TileType::Avif => 5,
+        }
+    }
+}
+
 impl TryInto<TileType> for u8 {
     type Error = PmtError;
 
@@ -139,19 +199,348 @@ impl TryInto<TileType> for u8 {
             2 => Ok(TileType::Png),
             3 => Ok(TileType::Jpeg),
             4 => Ok(TileType::Webp),
+            5 => Ok(TileType::Avif),
             _ => Err(PmtError::InvalidTileType),
         }
     }
 }
 
+impl Header {
+    /// Start building a new header for an archive with the given tile type and tile compression.
+    pub fn builder(tile_type: TileType, tile_compression: Compression) -> HeaderBuilder {
+        HeaderBuilder::new(tile_type, tile_compression)
+    }
+
+    /// Spec version the archive was written with.
+    ///
+    /// Some writers store the ASCII digit `'3'` instead of the number 3, which is reported as 3.
+    #[must_use]
+    pub fn spec_version(&self) -> u8 {
+        if self.version == b'3' {
+            3
+        } else {
+            self.version
+        }
+    }
+
+    /// Fails with [`PmtError::UnsupportedPmTilesVersion`] unless this is a spec version 3 header.
+    ///
+    /// Headers declaring other versions are still parsed as v3 headers when their layout allows it,
+    /// so this is for callers that prefer to reject such archives outright.
+    pub fn check_spec_version(&self) -> PmtResult<()> {
+        if self.spec_version() == 3 {
+            Ok(())
+        } else {
+            Err(PmtError::UnsupportedPmTilesVersion)
+        }
+    }
+
+    /// Offset of the root directory, in bytes from the start of the archive.
+    #[must_use]
+    pub fn root_offset(&self) -> u64 {
+        self.root_offset
+    }
+
+    /// Length of the root directory in bytes.
+    #[must_use]
+    pub fn root_length(&self) -> u64 {
+        self.root_length
+    }
+
+    /// Offset of the JSON metadata, in bytes from the start of the archive.
+    #[must_use]
+    pub fn metadata_offset(&self) -> u64 {
+        self.metadata_offset
+    }
+
+    /// Length of the JSON metadata in bytes.
+    #[must_use]
+    pub fn metadata_length(&self) -> u64 {
+        self.metadata_length
+    }
+
+    /// Offset of the leaf directories section, in bytes from the start of the archive.
+    #[must_use]
+    pub fn leaf_offset(&self) -> u64 {
+        self.leaf_offset
+    }
+
+    /// Length of the leaf directories section in bytes.
+    #[must_use]
+    pub fn leaf_length(&self) -> u64 {
+        self.leaf_length
+    }
+
+    /// Offset of the tile data section, in bytes from the start of the archive.
+    #[must_use]
+    pub fn data_offset(&self) -> u64 {
+        self.data_offset
+    }
+
+    /// Length of the tile data section in bytes.
+    #[must_use]
+    pub fn data_length(&self) -> u64 {
+        self.data_length
+    }
+
+    /// Number of tiles addressed by the archive, if known.
+    #[must_use]
+    pub fn n_addressed_tiles(&self) -> Option<NonZeroU64> {
+        self.n_addressed_tiles
+    }
+
+    /// Number of tile entries in all directories, if known.
+    #[must_use]
+    pub fn n_tile_entries(&self) -> Option<NonZeroU64> {
+        self.n_tile_entries
+    }
+
+    /// Number of distinct tile contents, if known.
+    #[must_use]
+    pub fn n_tile_contents(&self) -> Option<NonZeroU64> {
+        self.n_tile_contents
+    }
+
+    /// Whether tile data is ordered by tile ID.
+    #[must_use]
+    pub fn clustered(&self) -> bool {
+        self.clustered
+    }
+
+    /// Compression used for directories and metadata.
+    #[must_use]
+    pub fn internal_compression(&self) -> Compression {
+        self.internal_compression
+    }
+}
+
+impl Header {
+    /// Whether `zoom` falls within the archive's zoom range.
+    #[must_use]
+    pub fn contains_zoom(&self, zoom: u8) -> bool {
+        (self.min_zoom..=self.max_zoom).contains(&zoom)
+    }
+
+    /// Whether a longitude/latitude point falls within the archive's bounds.
+    #[must_use]
+    pub fn contains_lon_lat(&self, longitude: f64, latitude: f64) -> bool {
+        (self.min_longitude..=self.max_longitude).contains(&longitude)
+            && (self.min_latitude..=self.max_latitude).contains(&latitude)
+    }
+
+    /// Whether the tile could be present in this archive, judging only by the header's
+    /// zoom range and bounds. Use this to reject requests before any directory is read,
+    /// as `AsyncPmTilesReader::with_bounds_check` does.
+    ///
+    /// A `false` result means the tile is outside the area the archive declares,
+    /// a `true` result does not guarantee the tile exists.
+    #[must_use]
+    pub fn contains_tile(&self, coord: TileCoord) -> bool {
+        if !self.contains_zoom(coord.z()) {
+            return false;
+        }
+        let (min_lon, min_lat, max_lon, max_lat) = coord.bounds();
+        min_lon < self.max_longitude
+            && max_lon > self.min_longitude
+            && min_lat < self.max_latitude
+            && max_lat > self.min_latitude
+    }
+}
+
+/// Builder for constructing a [`Header`] programmatically.
+///
+/// Defaults to spec version 3, gzip internal compression, a clustered archive,
+/// zoom level 0 only, and world bounds.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct HeaderBuilder {
+    header: Header,
+}
+
+impl HeaderBuilder {
+    pub fn new(tile_type: TileType, tile_compression: Compression) -> Self {
+        Self {
+            header: Header {
+                version: 3,
+                root_offset: 0,
+                root_length: 0,
+                metadata_offset: 0,
+                metadata_length: 0,
+                leaf_offset: 0,
+                leaf_length: 0,
+                data_offset: 0,
+                data_length: 0,
+                n_addressed_tiles: None,
+                n_tile_entries: None,
+                n_tile_contents: None,
+                clustered: true,
+                internal_compression: Compression::Gzip,
+                tile_compression,
+                tile_type,
+                min_zoom: 0,
+                max_zoom: 0,
+                min_longitude: -180.0,
+                min_latitude: -85.0,
+                max_longitude: 180.0,
+                max_latitude: 85.0,
+                center_zoom: 0,
+                center_longitude: 0.0,
+                center_latitude: 0.0,
+            },
+        }
+    }
+
+    /// Fails with [`PmtError::UnsupportedPmTilesVersion`] unless `version` is 3,
+    /// as only version 3 headers can be written, see [`Header::check_spec_version`].
+    pub fn spec_version(mut self, version: u8) -> PmtResult<Self> {
+        if version != 3 {
+            return Err(PmtError::UnsupportedPmTilesVersion);
+        }
+        self.header.version = version;
+        Ok(self)
+    }
+
+    pub fn root_directory(mut self, offset: u64, length: u64) -> Self {
+        self.header.root_offset = offset;
+        self.header.root_length = length;
+        self
+    }
+
+    pub fn metadata(mut self, offset: u64, length: u64) -> Self {
+        self.header.metadata_offset = offset;
+        self.header.metadata_length = length;
+        self
+    }
+
+    pub fn leaf_directories(mut self, offset: u64, length: u64) -> Self {
+        self.header.leaf_offset = offset;
+        self.header.leaf_length = length;
+        self
+    }
+
+    pub fn tile_data(mut self, offset: u64, length: u64) -> Self {
+        self.header.data_offset = offset;
+        self.header.data_length = length;
+        self
+    }
+
+    /// Set the tile counters. A value of `0` means unknown.
+    pub fn counts(mut self, addressed_tiles: u64, tile_entries: u64, tile_contents: u64) -> Self {
+        self.header.n_addressed_tiles = NonZeroU64::new(addressed_tiles);
+        self.header.n_tile_entries = NonZeroU64::new(tile_entries);
+        self.header.n_tile_contents = NonZeroU64::new(tile_contents);
+        self
+    }
+
+    pub fn clustered(mut self, clustered: bool) -> Self {
+        self.header.clustered = clustered;
+        self
+    }
+
+    pub fn internal_compression(mut self, compression: Compression) -> Self {
+        self.header.internal_compression = compression;
+        self
+    }
+
+    pub fn zoom_range(mut self, min_zoom: u8, max_zoom: u8) -> Self {
+        self.header.min_zoom = min_zoom;
+        self.header.max_zoom = max_zoom;
+        self
+    }
+
+    pub fn bounds(
+        mut self,
+        min_longitude: f64,
+        min_latitude: f64,
+        max_longitude: f64,
+        max_latitude: f64,
+    ) -> Self {
+        self.header.min_longitude = min_longitude;
+        self.header.min_latitude = min_latitude;
+        self.header.max_longitude = max_longitude;
+        self.header.max_latitude = max_latitude;
+        self
+    }
+
+    pub fn center(mut self, zoom: u8, longitude: f64, latitude: f64) -> Self {
+        self.header.center_zoom = zoom;
+        self.header.center_longitude = longitude;
+        self.header.center_latitude = latitude;
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> Header {
+        self.header
+    }
+}
+
 static V3_MAGIC: &str = "PMTiles";
 static V2_MAGIC: &str = "PM";
 
 impl Header {
-    #[allow(clippy::cast_precision_loss)]
-    fn read_coordinate_part<B: Buf>(mut buf: B) -> f32 {
-        // TODO: would it be more precise to do `((value as f64) / 10_000_000.) as f32` ?
-        buf.get_i32_le() as f32 / 10_000_000.
+    /// Byte ranges of the archive's sections, as declared by this header.
+    #[must_use]
+    pub fn layout(&self) -> ArchiveLayout {
+        let range = |offset: u64, length: u64| offset..offset.saturating_add(length);
+        ArchiveLayout {
+            header: 0..HEADER_SIZE as u64,
+            root_directory: range(self.root_offset, self.root_length),
+            metadata: range(self.metadata_offset, self.metadata_length),
+            leaf_directories: range(self.leaf_offset, self.leaf_length),
+            tile_data: range(self.data_offset, self.data_length),
+        }
+    }
+
+    /// Coordinates are stored as integer multiples of 10<sup>-7</sup> degrees.
+    fn read_coordinate_part<B: Buf>(mut buf: B) -> f64 {
+        f64::from(buf.get_i32_le()) / 10_000_000.
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn write_coordinate_part<W: Write>(writer: &mut W, value: f64) -> std::io::Result<()> {
+        let value = (value * 10_000_000.).round() as i32;
+        writer.write_all(&value.to_le_bytes())
+    }
+
+    /// Writes the 127-byte v3 encoding of this header, the inverse of [`Header::try_from_bytes`].
+    ///
+    /// The version is always written as the number 3, see [`Header::check_spec_version`].
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> PmtResult<()> {
+        self.check_spec_version()?;
+        writer.write_all(V3_MAGIC.as_bytes())?;
+        writer.write_all(&[3])?;
+        for value in [
+            self.root_offset,
+            self.root_length,
+            self.metadata_offset,
+            self.metadata_length,
+            self.leaf_offset,
+            self.leaf_length,
+            self.data_offset,
+            self.data_length,
+            self.n_addressed_tiles.map_or(0, NonZeroU64::get),
+            self.n_tile_entries.map_or(0, NonZeroU64::get),
+            self.n_tile_contents.map_or(0, NonZeroU64::get),
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&[
+            u8::from(self.clustered),
+            self.internal_compression.into(),
+            self.tile_compression.into(),
+            self.tile_type.into(),
+            self.min_zoom,
+            self.max_zoom,
+        ])?;
+        Self::write_coordinate_part(writer, self.min_longitude)?;
+        Self::write_coordinate_part(writer, self.min_latitude)?;
+        Self::write_coordinate_part(writer, self.max_longitude)?;
+        Self::write_coordinate_part(writer, self.max_latitude)?;
+        writer.write_all(&[self.center_zoom])?;
+        Self::write_coordinate_part(writer, self.center_longitude)?;
+        Self::write_coordinate_part(writer, self.center_latitude)?;
+        Ok(())
     }
 
     pub fn try_from_bytes(mut bytes: Bytes) -> PmtResult<Self> {
@@ -209,8 +598,9 @@ mod tests {
 
     use bytes::{Bytes, BytesMut};
 
-    use crate::header::{Header, TileType, HEADER_SIZE};
+    use crate::header::{Compression, Header, TileType, HEADER_SIZE, MAX_INITIAL_BYTES};
     use crate::tests::{RASTER_FILE, VECTOR_FILE};
+    use crate::{PmtError, TileCoord};
 
     #[test]
     fn read_header() {
@@ -220,7 +610,10 @@ mod tests {
 
         let header = Header::try_from_bytes(Bytes::copy_from_slice(&header_bytes)).unwrap();
 
-        // TODO: should be 3, but currently the ascii char 3, assert_eq!(header.version, 3);
+        // The version is stored as the ASCII digit '3'
+        assert_eq!(header.version, b'3');
+        assert_eq!(header.spec_version(), 3);
+        header.check_spec_version().unwrap();
         assert_eq!(header.tile_type, TileType::Png);
         assert_eq!(header.n_addressed_tiles, NonZeroU64::new(85));
         assert_eq!(header.n_tile_entries, NonZeroU64::new(84));
@@ -253,15 +646,125 @@ mod tests {
         assert_eq!(header.min_zoom, 0);
         assert_eq!(header.max_zoom, 14);
         assert_eq!(header.center_zoom, 0);
-        assert_eq!(header.center_latitude, 43.779778);
-        assert_eq!(header.center_longitude, 11.241483);
-        assert_eq!(header.min_latitude, 43.727013);
-        assert_eq!(header.max_latitude, 43.832542);
+        assert_eq!(header.center_latitude, 43.779779);
+        assert_eq!(header.center_longitude, 11.2414827);
+        assert_eq!(header.min_latitude, 43.7270125);
+        assert_eq!(header.max_latitude, 43.8325455);
         assert_eq!(header.min_longitude, 11.154026);
-        assert_eq!(header.max_longitude, 11.328939);
+        assert_eq!(header.max_longitude, 11.3289395);
         assert!(header.clustered);
     }
 
+    #[test]
+    fn build_header() {
+        let header = Header::builder(TileType::Mvt, Compression::Gzip)
+            .root_directory(127, 10)
+            .metadata(137, 20)
+            .tile_data(157, 1000)
+            .counts(5, 4, 3)
+            .clustered(false)
+            .zoom_range(2, 14)
+            .bounds(11.1, 43.7, 11.3, 43.8)
+            .center(10, 11.2, 43.75)
+            .build();
+
+        assert_eq!(header.spec_version(), 3);
+        assert_eq!(header.root_offset(), 127);
+        assert_eq!(header.root_length(), 10);
+        assert_eq!(header.metadata_offset(), 137);
+        assert_eq!(header.metadata_length(), 20);
+        assert_eq!(header.leaf_length(), 0);
+        assert_eq!(header.data_offset(), 157);
+        assert_eq!(header.data_length(), 1000);
+        assert_eq!(header.n_addressed_tiles(), NonZeroU64::new(5));
+        assert_eq!(header.n_tile_entries(), NonZeroU64::new(4));
+        assert_eq!(header.n_tile_contents(), NonZeroU64::new(3));
+        assert!(!header.clustered());
+        assert_eq!(header.internal_compression(), Compression::Gzip);
+        assert_eq!(header.tile_compression, Compression::Gzip);
+        assert_eq!(header.tile_type, TileType::Mvt);
+        assert_eq!(header.min_zoom, 2);
+        assert_eq!(header.max_zoom, 14);
+        assert_eq!(header.center_zoom, 10);
+        assert_eq!(header.max_latitude, 43.8);
+    }
+
+    #[test]
+    fn write_header_round_trip() {
+        for file in [RASTER_FILE, VECTOR_FILE] {
+            let mut expected = vec![0; HEADER_SIZE];
+            File::open(file).unwrap().read_exact(&mut expected).unwrap();
+            let header = Header::try_from_bytes(Bytes::from(expected.clone())).unwrap();
+
+            let mut written = Vec::new();
+            header.write_to(&mut written).unwrap();
+            assert_eq!(written.len(), HEADER_SIZE);
+            assert_eq!(written[..7], expected[..7]);
+            assert_eq!(written[7], 3);
+            assert_eq!(written[8..], expected[8..]);
+            let mut reread = Header::try_from_bytes(Bytes::from(written)).unwrap();
+            assert_eq!(reread.spec_version(), header.spec_version());
+            reread.version = header.version;
+            assert_eq!(format!("{reread:?}"), format!("{header:?}"));
+        }
+    }
+
+    #[test]
+    fn unsupported_spec_version() {
+        let builder = Header::builder(TileType::Mvt, Compression::Gzip);
+        assert!(matches!(
+            builder.clone().spec_version(4),
+            Err(PmtError::UnsupportedPmTilesVersion)
+        ));
+        let mut header = builder.spec_version(3).unwrap().build();
+        assert!(header.check_spec_version().is_ok());
+
+        header.version = 4;
+        assert_eq!(header.spec_version(), 4);
+        assert!(matches!(
+            header.check_spec_version(),
+            Err(PmtError::UnsupportedPmTilesVersion)
+        ));
+        assert!(header.write_to(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn layout() {
+        let mut file = File::open(RASTER_FILE).unwrap();
+        let mut header_bytes = BytesMut::zeroed(HEADER_SIZE);
+        file.read_exact(header_bytes.as_mut()).unwrap();
+        let header = Header::try_from_bytes(header_bytes.freeze()).unwrap();
+
+        let layout = header.layout();
+        assert_eq!(layout.header, 0..127);
+        assert_eq!(layout.root_directory.start, 127);
+        assert_eq!(layout.root_directory.end, layout.metadata.start);
+        assert!(layout.root_directory.end <= MAX_INITIAL_BYTES as u64);
+        assert_eq!(layout.end(), file.metadata().unwrap().len());
+    }
+
+    #[test]
+    fn header_contains() {
+        let mut test = File::open(VECTOR_FILE).unwrap();
+        let mut header_bytes = BytesMut::zeroed(HEADER_SIZE);
+        test.read_exact(header_bytes.as_mut()).unwrap();
+        let header = Header::try_from_bytes(header_bytes.freeze()).unwrap();
+
+        assert!(header.contains_zoom(0));
+        assert!(header.contains_zoom(14));
+        assert!(!header.contains_zoom(15));
+
+        assert!(header.contains_lon_lat(11.25, 43.77));
+        assert!(!header.contains_lon_lat(2.35, 48.85));
+
+        let contains_tile = |z, x, y| header.contains_tile(TileCoord::new(z, x, y).unwrap());
+        assert!(contains_tile(0, 0, 0));
+        assert!(contains_tile(12, 2174, 1492));
+        assert!(!contains_tile(12, 0, 0));
+        assert!(!contains_tile(6, 31, 23));
+        assert!(!contains_tile(15, 17398, 11936));
+    }
+
     #[test]
     #[cfg(feature = "tilejson")]
     fn get_tilejson_raster() {
@@ -288,19 +791,11 @@ mod tests {
         let header = Header::try_from_bytes(header_bytes.freeze()).unwrap();
         let tj = header.get_tilejson(Vec::new());
 
-        assert_eq!(
-            tj.center,
-            Some(Center::new(11.241482734680176, 43.77977752685547, 0))
-        );
+        assert_eq!(tj.center, Some(Center::new(11.2414827, 43.779779, 0)));
 
         assert_eq!(
             tj.bounds,
-            Some(Bounds::new(
-                11.15402603149414,
-                43.727012634277344,
-                11.328939437866211,
-                43.832542419433594
-            ))
+            Some(Bounds::new(11.154026, 43.7270125, 11.3289395, 43.8325455))
         );
     }
 }
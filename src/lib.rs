@@ -4,6 +4,10 @@
 pub mod async_reader;
 #[cfg(feature = "__async-aws-s3")]
 mod backend_aws_s3;
+#[cfg(feature = "__async")]
+mod backend_boxed;
+#[cfg(feature = "__async")]
+mod backend_concurrency_limit;
 #[cfg(feature = "http-async")]
 mod backend_http;
 #[cfg(feature = "mmap-async-tokio")]
@@ -11,24 +15,58 @@ mod backend_mmap;
 #[cfg(feature = "__async-s3")]
 mod backend_s3;
 #[cfg(feature = "__async")]
+mod backend_throttled;
+mod bbox;
+#[cfg(feature = "__async")]
 pub mod cache;
+#[cfg(feature = "__async")]
+pub mod codec;
+#[cfg(feature = "__async")]
+pub mod copy;
 mod directory;
+#[cfg(feature = "__async")]
+mod entry_cache;
 mod error;
+#[cfg(feature = "__async")]
+pub mod export;
 mod header;
 #[cfg(feature = "__async")]
+mod refresh;
+mod sniff;
+#[cfg(feature = "__async")]
+pub mod stats;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
 mod tile;
+#[cfg(feature = "tile-source")]
+mod tile_source;
 
 #[cfg(feature = "aws-s3-async")]
 pub use backend_aws_s3::AwsS3Backend;
+#[cfg(feature = "__async")]
+pub use backend_boxed::BoxedBackend;
+#[cfg(feature = "__async")]
+pub use backend_concurrency_limit::ConcurrencyLimitBackend;
 #[cfg(feature = "http-async")]
 pub use backend_http::HttpBackend;
 #[cfg(feature = "mmap-async-tokio")]
 pub use backend_mmap::MmapBackend;
 #[cfg(feature = "__async-s3")]
 pub use backend_s3::S3Backend;
-pub use directory::{DirEntry, Directory};
-pub use error::{PmtError, PmtResult};
-pub use header::{Compression, Header, TileType};
+#[cfg(feature = "__async")]
+pub use backend_throttled::ThrottledBackend;
+pub use bbox::BoundingBox;
+pub use directory::{DirEntry, DirEntryCoordsIter, Directory};
+pub use error::{BackendError, PmtError, PmtResult};
+pub use header::{
+    ArchiveLayout, Compression, Header, HeaderBuilder, TileType, HEADER_SIZE, MAX_INITIAL_BYTES,
+};
+#[cfg(feature = "__async")]
+pub use refresh::RefreshingReader;
+pub use sniff::ImageInfo;
+pub use tile::{TileCoord, TileId, MAX_ZOOM};
+#[cfg(feature = "tile-source")]
+pub use tile_source::{TileSource, TileSourceFuture};
 //
 // Re-export crates exposed in our API to simplify dependency management
 #[cfg(feature = "__async-aws-s3")]
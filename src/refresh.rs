@@ -0,0 +1,207 @@
+use std::future::Future;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use crate::async_reader::{AsyncBackend, AsyncPmTilesReader, HealthStatus};
+use crate::cache::DirectoryCache;
+use crate::error::PmtResult;
+
+/// Serves an archive that is republished periodically, swapping in a new reader once it changes.
+///
+/// [`refresh`](Self::refresh) checks whether the archive has changed, see
+/// [`AsyncPmTilesReader::health_check`]. If so, it opens the archive again, looks up the tiles
+/// recently requested from the old reader in the new one, and only then swaps it in, so requests
+/// for hot tiles don't all miss the cache at once. This needs the old reader's
+/// [entry cache](AsyncPmTilesReader::with_entry_cache), which the new reader should have as well.
+///
+/// [`refresh_every`](Self::refresh_every) does this on an interval, for running in a background task.
+/// Clones share the current reader.
+pub struct RefreshingReader<B, C> {
+    current: Arc<RwLock<AsyncPmTilesReader<B, C>>>,
+}
+
+impl<B, C> Clone for RefreshingReader<B, C> {
+    fn clone(&self) -> Self {
+        Self {
+            current: Arc::clone(&self.current),
+        }
+    }
+}
+
+impl<B: AsyncBackend + Sync + Send, C: DirectoryCache + Sync + Send> RefreshingReader<B, C> {
+    /// Serves `reader` until the first refresh.
+    /// Warming needs its [entry cache](AsyncPmTilesReader::with_entry_cache).
+    pub fn new(reader: AsyncPmTilesReader<B, C>) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(reader)),
+        }
+    }
+
+    /// The reader for the archive as of the last refresh. Readers are cheap to clone.
+    pub fn current(&self) -> AsyncPmTilesReader<B, C> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Swaps in a reader opened with `reopen` if the archive has changed, returning whether it did.
+    ///
+    /// Backends such as [`HttpBackend`](crate::HttpBackend) only read the archive they first saw,
+    /// so `reopen` should create a new backend. Warming the new reader is best effort:
+    /// it is swapped in even if some of the lookups fail.
+    /// Fails if the archive could not be checked or reopened, keeping the current reader.
+    pub async fn refresh<F, Fut>(&self, reopen: F) -> PmtResult<bool>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = PmtResult<AsyncPmTilesReader<B, C>>>,
+    {
+        let current = self.current();
+        // The health check clears the entry cache when the archive has changed
+        let hot_tile_ids = current.hot_tile_ids();
+        match current.health_check().await {
+            HealthStatus::Ok => return Ok(false),
+            HealthStatus::Unreachable(e) => return Err(e),
+            HealthStatus::Changed => {}
+        }
+
+        let reader = reopen().await?;
+        #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+        let warmed = reader.warm_tiles(&hot_tile_ids).await;
+        #[cfg(feature = "tracing")]
+        if let Err(e) = warmed {
+            tracing::warn!(error = %e, "warming the refreshed archive failed");
+        }
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = reader;
+        Ok(true)
+    }
+
+    /// Calls [`refresh`](Self::refresh) every `interval`, forever.
+    ///
+    /// Failed refreshes are retried at the next interval, while the current reader is kept.
+    /// The returned future never completes, so spawn it as a background task and abort it when done.
+    /// The Tokio runtime must have its time driver enabled.
+    pub async fn refresh_every<F, Fut>(&self, interval: Duration, mut reopen: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = PmtResult<AsyncPmTilesReader<B, C>>>,
+    {
+        loop {
+            tokio::time::sleep(interval).await;
+            #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+            let refreshed = self.refresh(&mut reopen).await;
+            #[cfg(feature = "tracing")]
+            match refreshed {
+                Ok(true) => tracing::info!("swapped in the refreshed archive"),
+                Ok(false) => {}
+                Err(e) => tracing::warn!(error = %e, "refreshing the archive failed"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-utils")]
+mod tests {
+    use std::num::NonZeroUsize;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use super::RefreshingReader;
+    use crate::async_reader::{AsyncBackend, AsyncPmTilesReader};
+    use crate::cache::HashMapCache;
+    use crate::error::PmtResult;
+    use crate::test_utils::ArchiveBuilder;
+    use crate::{TileCoord, TileId, TileType};
+
+    /// Serves whatever archive was published last, like a bucket that is written to periodically.
+    #[derive(Clone, Default)]
+    struct Published(Arc<Mutex<Bytes>>);
+
+    impl Published {
+        async fn publish(&self, contents: &'static str) {
+            let archive = ArchiveBuilder::new(TileType::Png)
+                .zoom_levels(0..=2)
+                .tile(TileCoord::new(0, 0, 0).unwrap(), contents)
+                .leaf_size(4)
+                .build()
+                .await
+                .unwrap();
+            *self.0.lock().unwrap() = archive;
+        }
+
+        async fn open(self) -> PmtResult<AsyncPmTilesReader<Self, HashMapCache>> {
+            Ok(
+                AsyncPmTilesReader::try_from_cached_source(self, HashMapCache::default())
+                    .await?
+                    .with_entry_cache(NonZeroUsize::new(16).unwrap()),
+            )
+        }
+    }
+
+    impl AsyncBackend for Published {
+        async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+            let archive = self.0.lock().unwrap().clone();
+            let end = offset.saturating_add(length).min(archive.len());
+            Ok(archive.slice(offset.min(end)..end))
+        }
+    }
+
+    #[tokio::test]
+    async fn swaps_in_changed_archive() {
+        let published = Published::default();
+        published.publish("v1").await;
+        let reader = RefreshingReader::new(published.clone().open().await.unwrap());
+        let reopen = || published.clone().open();
+
+        assert_eq!(
+            reader.current().get_tile(0, 0, 0).await.unwrap().unwrap(),
+            "v1"
+        );
+        reader.current().get_tile(2, 1, 3).await.unwrap();
+        assert!(!reader.refresh(reopen).await.unwrap());
+
+        published.publish("v2, updated").await;
+        assert!(reader.refresh(reopen).await.unwrap());
+        let current = reader.current();
+        // The hot tiles were looked up before the new reader was swapped in
+        let hot_tile_ids = [(0, 0, 0), (2, 1, 3)]
+            .map(|(z, x, y)| TileId::from(TileCoord::new(z, x, y).unwrap()).value());
+        assert_eq!(current.hot_tile_ids(), hot_tile_ids);
+        assert_eq!(
+            current.get_tile(0, 0, 0).await.unwrap().unwrap(),
+            "v2, updated"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refreshes_on_interval() {
+        let published = Published::default();
+        published.publish("v1").await;
+        let reader = RefreshingReader::new(published.clone().open().await.unwrap());
+
+        let background = reader.clone();
+        let source = published.clone();
+        let reopen = move || source.clone().open();
+        let task = tokio::spawn(async move {
+            background
+                .refresh_every(Duration::from_secs(60), reopen)
+                .await;
+        });
+
+        published.publish("v2, updated").await;
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        assert_eq!(
+            reader.current().get_tile(0, 0, 0).await.unwrap().unwrap(),
+            "v1"
+        );
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(
+            reader.current().get_tile(0, 0, 0).await.unwrap().unwrap(),
+            "v2, updated"
+        );
+        task.abort();
+    }
+}
@@ -0,0 +1,208 @@
+use crate::header::TileType;
+
+/// Format and dimensions of a raster tile, detected from its leading bytes.
+///
+/// Useful to check tile contents against the archive's [`Header::tile_type`](crate::Header::tile_type),
+/// as mislabelled archives otherwise only show up as tiles failing to render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub tile_type: TileType,
+    /// Width and height in pixels, if they could be read.
+    pub dimensions: Option<(u32, u32)>,
+}
+
+impl ImageInfo {
+    /// Detects PNG, JPEG, WebP and AVIF images by their signatures.
+    /// Returns `None` for any other data, including compressed or vector tiles.
+    #[must_use]
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        let (tile_type, dimensions) = if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            (TileType::Png, png_dimensions(data))
+        } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            (TileType::Jpeg, jpeg_dimensions(data))
+        } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+            (TileType::Webp, webp_dimensions(data))
+        } else if is_avif(data) {
+            (TileType::Avif, avif_dimensions(data))
+        } else {
+            return None;
+        };
+
+        Some(Self {
+            tile_type,
+            dimensions,
+        })
+    }
+}
+
+fn u16_be(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from(u16::from_be_bytes(
+        data.get(at..at + 2)?.try_into().ok()?,
+    )))
+}
+
+fn u32_be(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u16_le(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from(u16::from_le_bytes(
+        data.get(at..at + 2)?.try_into().ok()?,
+    )))
+}
+
+fn u24_le(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+/// Reads the IHDR chunk, which must come first.
+fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    (data.get(12..16)? == b"IHDR").then_some(())?;
+    Some((u32_be(data, 16)?, u32_be(data, 20)?))
+}
+
+/// Walks the marker segments up to the first start-of-frame segment.
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut pos = 2;
+    loop {
+        // Markers may be preceded by any number of fill bytes
+        while *data.get(pos)? == 0xFF && *data.get(pos + 1)? == 0xFF {
+            pos += 1;
+        }
+        (*data.get(pos)? == 0xFF).then_some(())?;
+        let marker = *data.get(pos + 1)?;
+        match marker {
+            // Standalone markers without a length
+            0x01 | 0xD0..=0xD7 => pos += 2,
+            // Start of frame, excluding DHT, JPG and DAC which share the range
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                return Some((u16_be(data, pos + 7)?, u16_be(data, pos + 5)?));
+            }
+            0xD9 | 0xDA => return None,
+            _ => pos += 2 + usize::try_from(u16_be(data, pos + 2)?).ok()?,
+        }
+    }
+}
+
+fn webp_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    match data.get(12..16)? {
+        // Lossy: frame header after the 3-byte frame tag and 3-byte start code
+        b"VP8 " => Some((u16_le(data, 26)? & 0x3FFF, u16_le(data, 28)? & 0x3FFF)),
+        // Lossless: 14-bit width and height minus one, after the 1-byte signature
+        b"VP8L" => {
+            let bits = u32::from_le_bytes(data.get(21..25)?.try_into().ok()?);
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        // Extended: 24-bit canvas width and height minus one
+        b"VP8X" => Some((u24_le(data, 24)? + 1, u24_le(data, 27)? + 1)),
+        _ => None,
+    }
+}
+
+/// Checks the major and compatible brands of the leading `ftyp` box.
+fn is_avif(data: &[u8]) -> bool {
+    if data.get(4..8) != Some(b"ftyp") {
+        return false;
+    }
+    let Some(size) = u32_be(data, 0).and_then(|size| usize::try_from(size).ok()) else {
+        return false;
+    };
+    let brands = data.get(8..size.min(data.len())).unwrap_or_default();
+    brands
+        .chunks_exact(4)
+        .enumerate()
+        // The second word is the minor version, not a brand
+        .any(|(idx, brand)| idx != 1 && (brand == b"avif" || brand == b"avis"))
+}
+
+/// Reads the first image spatial extents (`ispe`) property.
+fn avif_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let pos = data.windows(4).position(|w| w == b"ispe")?;
+    // The box type is followed by a version and flags word
+    Some((u32_be(data, pos + 8)?, u32_be(data, pos + 12)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ImageInfo;
+    use crate::TileType;
+
+    fn sniff(data: &[u8]) -> Option<(TileType, Option<(u32, u32)>)> {
+        ImageInfo::sniff(data).map(|info| (info.tile_type, info.dimensions))
+    }
+
+    #[test]
+    fn png() {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend_from_slice(&256_u32.to_be_bytes());
+        data.extend_from_slice(&512_u32.to_be_bytes());
+        assert_eq!(sniff(&data), Some((TileType::Png, Some((256, 512)))));
+        assert_eq!(sniff(&data[..12]), Some((TileType::Png, None)));
+    }
+
+    #[test]
+    fn jpeg() {
+        let data = [
+            0xFF, 0xD8, // SOI
+            0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, // APP0 with two bytes of payload
+            0xFF, 0xFF, 0xC0, 0x00, 0x0B, 0x08, // fill byte, SOF0, length, precision
+            0x01, 0x00, 0x02, 0x00, // height 256, width 512
+        ];
+        assert_eq!(sniff(&data), Some((TileType::Jpeg, Some((512, 256)))));
+    }
+
+    #[test]
+    fn webp() {
+        let mut lossy = b"RIFF\0\0\0\0WEBPVP8 \0\0\0\0\0\0\0\x9d\x01\x2a".to_vec();
+        lossy.extend_from_slice(&[0x00, 0x01, 0x00, 0x02]);
+        assert_eq!(sniff(&lossy), Some((TileType::Webp, Some((256, 512)))));
+
+        let mut lossless = b"RIFF\0\0\0\0WEBPVP8L\0\0\0\0\x2f".to_vec();
+        lossless.extend_from_slice(&(255_u32 | (511 << 14)).to_le_bytes());
+        assert_eq!(sniff(&lossless), Some((TileType::Webp, Some((256, 512)))));
+
+        let mut extended = b"RIFF\0\0\0\0WEBPVP8X\0\0\0\0\0\0\0\0".to_vec();
+        extended.extend_from_slice(&[0xFF, 0x00, 0x00, 0xFF, 0x01, 0x00]);
+        assert_eq!(sniff(&extended), Some((TileType::Webp, Some((256, 512)))));
+    }
+
+    #[test]
+    fn avif() {
+        let mut data = b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf".to_vec();
+        data.extend_from_slice(b"\0\0\0\x14ispe\0\0\0\0");
+        data.extend_from_slice(&256_u32.to_be_bytes());
+        data.extend_from_slice(&512_u32.to_be_bytes());
+        assert_eq!(sniff(&data), Some((TileType::Avif, Some((256, 512)))));
+
+        // HEIC images share the container format
+        assert_eq!(sniff(b"\0\0\0\x18ftypheic\0\0\0\0mif1heic"), None);
+    }
+
+    #[test]
+    fn other_data() {
+        assert_eq!(sniff(b""), None);
+        assert_eq!(sniff(&[0x1F, 0x8B, 0x08, 0x00]), None);
+        assert_eq!(sniff(b"RIFF\0\0\0\0WAVE"), None);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mmap-async-tokio")]
+mod mmap_tests {
+    use super::ImageInfo;
+    use crate::async_reader::AsyncPmTilesReader;
+    use crate::tests::RASTER_FILE;
+    use crate::MmapBackend;
+
+    #[tokio::test]
+    async fn sniff_fixture_tile() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let tile = tiles.get_tile(2, 1, 1).await.unwrap().unwrap();
+
+        let info = ImageInfo::sniff(&tile).unwrap();
+        assert_eq!(info.tile_type, tiles.get_header().tile_type);
+        assert_eq!(info.dimensions, Some((256, 256)));
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::directory::DirEntry;
+use crate::error::{PmtError, PmtResult};
+use crate::tile::{tile_id, TileId, MAX_ZOOM};
+
+/// Statistics about the tiles and directories of an archive,
+/// see [`AsyncPmTilesReader::stats`](crate::async_reader::AsyncPmTilesReader::stats).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveStats {
+    /// Number of addressed tiles per zoom level, for zoom levels that have any tiles.
+    pub tiles_per_zoom: BTreeMap<u8, u64>,
+    /// Number of addressed tiles, counting every tile of a run separately.
+    pub addressed_tiles: u64,
+    /// Number of tile entries in all directories.
+    pub tile_entries: u64,
+    /// Number of distinct tile contents in the tile data section.
+    pub unique_tiles: u64,
+    /// Sizes of the distinct tile contents.
+    pub tile_sizes: SizeStats,
+    /// Share of addressed tiles reusing the contents of another tile, between 0 and 1.
+    pub duplicate_ratio: f64,
+    /// Number of leaf directories.
+    pub leaf_directories: u64,
+    /// Compressed sizes of the leaf directories.
+    pub leaf_directory_sizes: SizeStats,
+}
+
+/// Distribution of a set of byte sizes. All values are zero for an empty set.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SizeStats {
+    pub count: u64,
+    pub total: u64,
+    pub min: u32,
+    pub max: u32,
+    pub mean: f64,
+    pub p50: u32,
+    pub p90: u32,
+    pub p99: u32,
+}
+
+impl SizeStats {
+    #[allow(clippy::cast_precision_loss)]
+    fn from_sizes(mut sizes: Vec<u32>) -> Self {
+        sizes.sort_unstable();
+        let (Some(&min), Some(&max)) = (sizes.first(), sizes.last()) else {
+            return Self::default();
+        };
+        let count = sizes.len() as u64;
+        let total = sizes.iter().copied().map(u64::from).sum();
+        Self {
+            count,
+            total,
+            min,
+            max,
+            mean: total as f64 / count as f64,
+            p50: percentile(&sizes, 50),
+            p90: percentile(&sizes, 90),
+            p99: percentile(&sizes, 99),
+        }
+    }
+}
+
+/// Nearest-rank percentile of non-empty, sorted `sizes`.
+#[allow(clippy::cast_possible_truncation)]
+fn percentile(sizes: &[u32], pct: u64) -> u32 {
+    let rank = (sizes.len() as u64 * pct).div_ceil(100).max(1);
+    sizes[rank as usize - 1]
+}
+
+impl ArchiveStats {
+    /// Computes the statistics from all tile entries and all leaf directory entries of an archive.
+    pub(crate) fn from_entries(tiles: &[DirEntry], leaves: &[DirEntry]) -> PmtResult<Self> {
+        let mut tiles_per_zoom = BTreeMap::new();
+        let mut addressed_tiles = 0;
+        let mut contents = HashMap::new();
+
+        for entry in tiles {
+            let end = entry
+                .tile_id
+                .checked_add(u64::from(entry.run_length))
+                .ok_or(PmtError::InvalidEntry)?;
+            addressed_tiles += u64::from(entry.run_length);
+            contents.insert(entry.offset, entry.length);
+
+            // A run may cross into the next zoom level
+            let mut start = entry.tile_id;
+            while start < end {
+                let zoom = TileId::new(start)?.zoom();
+                let zoom_end = if zoom < MAX_ZOOM {
+                    tile_id(zoom + 1, 0, 0)
+                } else {
+                    u64::MAX
+                };
+                *tiles_per_zoom.entry(zoom).or_insert(0) += end.min(zoom_end) - start;
+                start = zoom_end;
+            }
+        }
+
+        let unique_tiles = contents.len() as u64;
+        #[allow(clippy::cast_precision_loss)]
+        let duplicate_ratio = if addressed_tiles == 0 {
+            0.0
+        } else {
+            1.0 - unique_tiles as f64 / addressed_tiles as f64
+        };
+
+        Ok(Self {
+            tiles_per_zoom,
+            addressed_tiles,
+            tile_entries: tiles.len() as u64,
+            unique_tiles,
+            tile_sizes: SizeStats::from_sizes(contents.into_values().collect()),
+            duplicate_ratio,
+            leaf_directories: leaves.len() as u64,
+            leaf_directory_sizes: SizeStats::from_sizes(leaves.iter().map(|e| e.length).collect()),
+        })
+    }
+}
+
+/// What a backend request made by the reader was for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReadPurpose {
+    /// The initial read of the header and root directory, including any prefetched bytes.
+    Header,
+    /// Leaf directories.
+    Directory,
+    /// Tile data.
+    Tile,
+    /// The JSON metadata.
+    Metadata,
+}
+
+/// Backend requests made by a reader, see
+/// [`AsyncPmTilesReader::io_stats`](crate::async_reader::AsyncPmTilesReader::io_stats).
+///
+/// Reads served from bytes the reader already holds are not counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoStats {
+    pub header: ReadStats,
+    pub directory: ReadStats,
+    pub tile: ReadStats,
+    pub metadata: ReadStats,
+}
+
+impl IoStats {
+    /// Statistics for requests made for `purpose`.
+    #[must_use]
+    pub fn get(&self, purpose: ReadPurpose) -> ReadStats {
+        match purpose {
+            ReadPurpose::Header => self.header,
+            ReadPurpose::Directory => self.directory,
+            ReadPurpose::Tile => self.tile,
+            ReadPurpose::Metadata => self.metadata,
+        }
+    }
+
+    /// Statistics summed over all purposes.
+    #[must_use]
+    pub fn total(&self) -> ReadStats {
+        [self.header, self.directory, self.tile, self.metadata]
+            .into_iter()
+            .fold(ReadStats::default(), |acc, s| ReadStats {
+                requests: acc.requests + s.requests,
+                bytes: acc.bytes + s.bytes,
+                errors: acc.errors + s.errors,
+            })
+    }
+}
+
+/// Counts of backend requests for one [`ReadPurpose`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadStats {
+    /// Number of requests, including failed ones.
+    pub requests: u64,
+    /// Number of bytes received by successful requests.
+    pub bytes: u64,
+    /// Number of failed requests.
+    pub errors: u64,
+}
+
+/// Atomic counters behind [`IoStats`], shared by all concurrent calls on a reader.
+#[derive(Debug, Default)]
+pub(crate) struct IoCounters([PurposeCounters; 4]);
+
+#[derive(Debug, Default)]
+struct PurposeCounters {
+    requests: AtomicU64,
+    bytes: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl IoCounters {
+    pub(crate) fn record<T: AsRef<[u8]>, E>(&self, purpose: ReadPurpose, result: &Result<T, E>) {
+        let counters = &self.0[purpose as usize];
+        counters.requests.fetch_add(1, Ordering::Relaxed);
+        match result {
+            Ok(bytes) => {
+                let length = bytes.as_ref().len() as u64;
+                counters.bytes.fetch_add(length, Ordering::Relaxed);
+            }
+            Err(_) => {
+                counters.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Records a streamed read of `length` bytes, which are counted once the stream is started.
+    pub(crate) fn record_stream<T, E>(
+        &self,
+        purpose: ReadPurpose,
+        length: usize,
+        result: &Result<T, E>,
+    ) {
+        let counters = &self.0[purpose as usize];
+        counters.requests.fetch_add(1, Ordering::Relaxed);
+        if result.is_ok() {
+            counters.bytes.fetch_add(length as u64, Ordering::Relaxed);
+        } else {
+            counters.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> IoStats {
+        let get = |purpose: ReadPurpose| {
+            let counters = &self.0[purpose as usize];
+            ReadStats {
+                requests: counters.requests.load(Ordering::Relaxed),
+                bytes: counters.bytes.load(Ordering::Relaxed),
+                errors: counters.errors.load(Ordering::Relaxed),
+            }
+        };
+        IoStats {
+            header: get(ReadPurpose::Header),
+            directory: get(ReadPurpose::Directory),
+            tile: get(ReadPurpose::Tile),
+            metadata: get(ReadPurpose::Metadata),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArchiveStats, IoCounters, ReadPurpose, ReadStats, SizeStats};
+    use crate::{DirEntry, PmtError};
+
+    #[test]
+    fn size_stats() {
+        assert_eq!(SizeStats::from_sizes(vec![]), SizeStats::default());
+
+        let stats = SizeStats::from_sizes((1..=100).rev().collect());
+        assert_eq!((stats.count, stats.total), (100, 5050));
+        assert_eq!((stats.min, stats.max), (1, 100));
+        assert!((stats.mean - 50.5).abs() < f64::EPSILON);
+        assert_eq!((stats.p50, stats.p90, stats.p99), (50, 90, 99));
+    }
+
+    #[test]
+    fn runs_across_zoom_levels() {
+        let tiles = [
+            DirEntry::new(0, 0, 10, 1),
+            // tile 3 at zoom 1 through tile 6 at zoom 2, all sharing contents
+            DirEntry::new(3, 10, 20, 4),
+        ];
+        let leaves = [DirEntry::new(0, 0, 50, 0)];
+
+        let stats = ArchiveStats::from_entries(&tiles, &leaves).unwrap();
+        assert_eq!(
+            stats.tiles_per_zoom.into_iter().collect::<Vec<_>>(),
+            [(0, 1), (1, 2), (2, 2)]
+        );
+        assert_eq!(stats.addressed_tiles, 5);
+        assert_eq!(stats.tile_entries, 2);
+        assert_eq!(stats.unique_tiles, 2);
+        assert!((stats.duplicate_ratio - 0.6).abs() < f64::EPSILON);
+        assert_eq!(stats.tile_sizes.total, 30);
+        assert_eq!(stats.leaf_directories, 1);
+        assert_eq!(stats.leaf_directory_sizes.max, 50);
+    }
+
+    #[test]
+    fn run_past_last_tile_id() {
+        let tiles = [DirEntry::new(u64::MAX - 1, 0, 10, 5)];
+        assert!(matches!(
+            ArchiveStats::from_entries(&tiles, &[]),
+            Err(PmtError::InvalidEntry)
+        ));
+    }
+
+    #[test]
+    fn io_counters() {
+        let counters = IoCounters::default();
+        counters.record::<_, ()>(ReadPurpose::Tile, &Ok([0_u8; 10]));
+        counters.record::<_, ()>(ReadPurpose::Tile, &Ok([0_u8; 5]));
+        counters.record::<[u8; 0], _>(ReadPurpose::Directory, &Err(()));
+
+        let stats = counters.snapshot();
+        let tile = ReadStats {
+            requests: 2,
+            bytes: 15,
+            errors: 0,
+        };
+        assert_eq!(stats.get(ReadPurpose::Tile), tile);
+        assert_eq!(stats.directory.errors, 1);
+        assert_eq!(stats.header, ReadStats::default());
+        assert_eq!(stats.total().requests, 3);
+    }
+}
@@ -0,0 +1,557 @@
+//! Archives and backends for testing code built on top of this crate, such as retry and caching layers.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::async_reader::{AccessPattern, AsyncBackend};
+use crate::codec::compress;
+use crate::directory::{DirEntry, Directory};
+use crate::error::{PmtError, PmtResult};
+use crate::header::HEADER_SIZE;
+use crate::{Compression, Header, TileCoord, TileId, TileType};
+
+/// Builds a valid `PMTiles` archive in memory.
+///
+/// ```
+/// # async fn example() -> pmtiles::PmtResult<()> {
+/// use pmtiles::async_reader::AsyncPmTilesReader;
+/// use pmtiles::test_utils::{ArchiveBuilder, MemoryBackend};
+/// use pmtiles::TileType;
+///
+/// let archive = ArchiveBuilder::new(TileType::Png)
+///     .zoom_levels(0..=3)
+///     .leaf_size(16)
+///     .build()
+///     .await?;
+/// let reader = AsyncPmTilesReader::try_from_source(MemoryBackend::new(archive)).await?;
+/// assert_eq!(reader.get_tile(3, 1, 2).await?.unwrap(), "3/1/2");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ArchiveBuilder {
+    tile_type: TileType,
+    tile_compression: Compression,
+    internal_compression: Compression,
+    metadata: String,
+    leaf_size: Option<usize>,
+    max_root_entries: Option<usize>,
+    tiles: BTreeMap<u64, Bytes>,
+}
+
+impl ArchiveBuilder {
+    /// An empty archive of `tile_type` tiles, with uncompressed tiles,
+    /// gzip-compressed directories and `{}` as metadata.
+    #[must_use]
+    pub fn new(tile_type: TileType) -> Self {
+        Self {
+            tile_type,
+            tile_compression: Compression::None,
+            internal_compression: Compression::Gzip,
+            metadata: "{}".to_string(),
+            leaf_size: None,
+            max_root_entries: None,
+            tiles: BTreeMap::new(),
+        }
+    }
+
+    /// Compression applied to the tile contents when building the archive.
+    #[must_use]
+    pub fn tile_compression(mut self, compression: Compression) -> Self {
+        self.tile_compression = compression;
+        self
+    }
+
+    /// Compression applied to the directories and metadata.
+    #[must_use]
+    pub fn internal_compression(mut self, compression: Compression) -> Self {
+        self.internal_compression = compression;
+        self
+    }
+
+    #[must_use]
+    pub fn metadata(mut self, metadata: impl Into<String>) -> Self {
+        self.metadata = metadata.into();
+        self
+    }
+
+    /// Moves the tile entries into leaf directories of at most `entries` entries each,
+    /// instead of keeping them all in the root directory.
+    /// Archives with more than a few thousand tile entries need leaf directories,
+    /// as readers expect the root directory within the first 16,384 bytes.
+    #[must_use]
+    pub fn leaf_size(mut self, entries: usize) -> Self {
+        self.leaf_size = Some(entries.max(1));
+        self
+    }
+
+    /// Nests further levels of leaf directories, each holding the leaf pointers of the level below,
+    /// until the root directory has at most `entries` entries. Only applies with [`Self::leaf_size`].
+    #[must_use]
+    pub fn max_root_entries(mut self, entries: usize) -> Self {
+        self.max_root_entries = Some(entries.max(1));
+        self
+    }
+
+    /// Adds a tile with the given contents, replacing any previous tile at `coord`.
+    #[must_use]
+    pub fn tile(mut self, coord: TileCoord, data: impl Into<Bytes>) -> Self {
+        self.tiles.insert(TileId::from(coord).value(), data.into());
+        self
+    }
+
+    /// Adds every tile of the given zoom levels, with `"{z}/{x}/{y}"` as contents.
+    #[must_use]
+    pub fn zoom_levels(mut self, zooms: RangeInclusive<u8>) -> Self {
+        for z in zooms {
+            let size = 1_u64 << z;
+            for (x, y) in (0..size).flat_map(|x| (0..size).map(move |y| (x, y))) {
+                if let Ok(coord) = TileCoord::new(z, x, y) {
+                    self = self.tile(coord, format!("{z}/{x}/{y}"));
+                }
+            }
+        }
+        self
+    }
+
+    /// Encodes the archive. Consecutive tiles with identical contents are stored once, as a run.
+    pub async fn build(self) -> PmtResult<Bytes> {
+        let mut entries: Vec<DirEntry> = Vec::new();
+        let mut data = Vec::new();
+        let mut last_contents = Bytes::new();
+        for (&tile_id, contents) in &self.tiles {
+            let contents = compress(self.tile_compression, contents.clone()).await?;
+            if let Some(last) = entries.last_mut() {
+                if last.tile_id + u64::from(last.run_length) == tile_id && last_contents == contents
+                {
+                    last.run_length += 1;
+                    continue;
+                }
+            }
+            entries.push(DirEntry::new(
+                tile_id,
+                data.len() as u64,
+                u32::try_from(contents.len()).map_err(|_| PmtError::InvalidEntry)?,
+                1,
+            ));
+            data.extend_from_slice(&contents);
+            last_contents = contents;
+        }
+
+        let mut leaves = Vec::new();
+        let mut root_entries = entries.clone();
+        if let Some(leaf_size) = self.leaf_size {
+            let (mut chunk_size, mut max_entries) = (leaf_size, leaf_size);
+            while root_entries.len() > max_entries {
+                let mut pointers = Vec::new();
+                for chunk in root_entries.chunks(chunk_size) {
+                    let leaf = self.encode_directory(chunk).await?;
+                    pointers.push(DirEntry::new(
+                        chunk[0].tile_id,
+                        leaves.len() as u64,
+                        u32::try_from(leaf.len()).map_err(|_| PmtError::InvalidEntry)?,
+                        0,
+                    ));
+                    leaves.extend_from_slice(&leaf);
+                }
+                root_entries = pointers;
+                // Leaves of single leaf pointers would never shrink the root directory
+                chunk_size = leaf_size.max(2);
+                max_entries = self.max_root_entries.unwrap_or(usize::MAX);
+            }
+        }
+        let root = self.encode_directory(&root_entries).await?;
+        let metadata = compress(
+            self.internal_compression,
+            Bytes::from(self.metadata.clone()),
+        )
+        .await?;
+
+        let root_offset = HEADER_SIZE as u64;
+        let metadata_offset = root_offset + root.len() as u64;
+        let leaf_offset = metadata_offset + metadata.len() as u64;
+        let data_offset = leaf_offset + leaves.len() as u64;
+        let zooms = self.tiles.keys().filter_map(|&id| TileId::new(id).ok());
+        let min_zoom = zooms.clone().map(TileId::zoom).min().unwrap_or_default();
+        let max_zoom = zooms.map(TileId::zoom).max().unwrap_or_default();
+
+        let header = Header::builder(self.tile_type, self.tile_compression)
+            .internal_compression(self.internal_compression)
+            .root_directory(root_offset, root.len() as u64)
+            .metadata(metadata_offset, metadata.len() as u64)
+            .leaf_directories(leaf_offset, leaves.len() as u64)
+            .tile_data(data_offset, data.len() as u64)
+            .counts(
+                entries.iter().map(|e| u64::from(e.run_length)).sum(),
+                entries.len() as u64,
+                entries.len() as u64,
+            )
+            .zoom_range(min_zoom, max_zoom)
+            .bounds(-180.0, -85.0, 180.0, 85.0)
+            .build();
+
+        let mut archive = Vec::new();
+        header.write_to(&mut archive)?;
+        archive.extend_from_slice(&root);
+        archive.extend_from_slice(&metadata);
+        archive.extend_from_slice(&leaves);
+        archive.extend_from_slice(&data);
+        Ok(Bytes::from(archive))
+    }
+
+    async fn encode_directory(&self, entries: &[DirEntry]) -> PmtResult<Bytes> {
+        let mut encoded = Vec::new();
+        Directory::from_entries(entries)?.write_to(&mut encoded)?;
+        compress(self.internal_compression, Bytes::from(encoded)).await
+    }
+}
+
+/// A backend serving an archive held in memory, such as one built with [`ArchiveBuilder`].
+#[derive(Debug, Clone)]
+pub struct MemoryBackend(Bytes);
+
+impl MemoryBackend {
+    pub fn new(archive: impl Into<Bytes>) -> Self {
+        Self(archive.into())
+    }
+}
+
+impl AsyncBackend for MemoryBackend {
+    async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        let start = offset.min(self.0.len());
+        let end = offset.saturating_add(length).min(self.0.len());
+        Ok(self.0.slice(start..end))
+    }
+
+    fn size(&self) -> Option<u64> {
+        Some(self.0.len() as u64)
+    }
+}
+
+/// Wraps another backend, injecting delays, transient errors and partial reads.
+///
+/// Faults are injected deterministically based on the number of reads made so far,
+/// counting both [`AsyncBackend::read`] and [`AsyncBackend::read_exact`] calls.
+/// Injected errors are [`io::ErrorKind::ConnectionReset`] errors, which are retryable.
+pub struct FlakyBackend<B> {
+    inner: B,
+    delay: Duration,
+    fail_every: usize,
+    truncate_every: usize,
+    fail_next: AtomicUsize,
+    reads: AtomicUsize,
+}
+
+impl<B> FlakyBackend<B> {
+    /// Wraps `inner` without injecting any faults.
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            delay: Duration::ZERO,
+            fail_every: 0,
+            truncate_every: 0,
+            fail_next: AtomicUsize::new(0),
+            reads: AtomicUsize::new(0),
+        }
+    }
+
+    /// Delays every read by `delay`. Requires the Tokio runtime to have its time driver enabled.
+    #[must_use]
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Fails every `n`th read, starting with the `n`th. Zero disables this fault.
+    #[must_use]
+    pub fn fail_every(mut self, n: usize) -> Self {
+        self.fail_every = n;
+        self
+    }
+
+    /// Returns only the first half of the requested bytes on every `n`th read,
+    /// starting with the `n`th. Zero disables this fault.
+    #[must_use]
+    pub fn truncate_every(mut self, n: usize) -> Self {
+        self.truncate_every = n;
+        self
+    }
+
+    /// Fails the next `n` reads, in addition to any other injected faults.
+    pub fn fail_next(&self, n: usize) {
+        self.fail_next.store(n, Ordering::Relaxed);
+    }
+
+    /// Number of reads made so far, including failed ones.
+    pub fn reads(&self) -> usize {
+        self.reads.load(Ordering::Relaxed)
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+fn is_nth(count: usize, n: usize) -> bool {
+    n != 0 && count % n == 0
+}
+
+impl<B: AsyncBackend + Sync + Send> AsyncBackend for FlakyBackend<B> {
+    // `read_exact` is left to the default implementation, so truncated reads surface as
+    // `PmtError::UnexpectedNumberOfBytesReturned` just like a misbehaving backend's would.
+    async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        let count = self.reads.fetch_add(1, Ordering::Relaxed) + 1;
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay).await;
+        }
+
+        let fail_next = self
+            .fail_next
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok();
+        if fail_next || is_nth(count, self.fail_every) {
+            return Err(PmtError::Reading(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                "injected fault",
+            )));
+        }
+
+        let mut data = self.inner.read(offset, length).await?;
+        if is_nth(count, self.truncate_every) {
+            data.truncate(data.len() / 2);
+        }
+        Ok(data)
+    }
+
+    fn resource(&self) -> Option<String> {
+        self.inner.resource()
+    }
+
+    fn size(&self) -> Option<u64> {
+        self.inner.size()
+    }
+
+    fn hint(&self, pattern: AccessPattern) {
+        self.inner.hint(pattern);
+    }
+}
+
+/// A read made through a [`RecordingBackend`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordedRead {
+    pub offset: usize,
+    pub length: usize,
+    /// Whether the read was made with [`AsyncBackend::read_exact`] rather than [`AsyncBackend::read`].
+    pub exact: bool,
+    /// Number of bytes returned, or `None` if the read failed.
+    pub received: Option<usize>,
+}
+
+/// Wraps another backend, recording every read made through it.
+pub struct RecordingBackend<B> {
+    inner: B,
+    reads: Mutex<Vec<RecordedRead>>,
+}
+
+impl<B> RecordingBackend<B> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            reads: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// All reads recorded so far, in the order they completed.
+    pub fn reads(&self) -> Vec<RecordedRead> {
+        self.lock().clone()
+    }
+
+    /// Forgets all recorded reads.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<RecordedRead>> {
+        self.reads.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn record(&self, offset: usize, length: usize, exact: bool, result: &PmtResult<Bytes>) {
+        self.lock().push(RecordedRead {
+            offset,
+            length,
+            exact,
+            received: result.as_ref().ok().map(Bytes::len),
+        });
+    }
+}
+
+impl<B: AsyncBackend + Sync + Send> AsyncBackend for RecordingBackend<B> {
+    async fn read_exact(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        let result = self.inner.read_exact(offset, length).await;
+        self.record(offset, length, true, &result);
+        result
+    }
+
+    async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        let result = self.inner.read(offset, length).await;
+        self.record(offset, length, false, &result);
+        result
+    }
+
+    fn resource(&self) -> Option<String> {
+        self.inner.resource()
+    }
+
+    fn size(&self) -> Option<u64> {
+        self.inner.size()
+    }
+
+    fn hint(&self, pattern: AccessPattern) {
+        self.inner.hint(pattern);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArchiveBuilder, MemoryBackend};
+    use crate::async_reader::AsyncPmTilesReader;
+    use crate::{Compression, PmtError, TileCoord, TileType};
+
+    #[tokio::test]
+    async fn build_archive() {
+        for leaf_size in [None, Some(1), Some(7)] {
+            for internal in [Compression::Gzip, Compression::None] {
+                let mut builder = ArchiveBuilder::new(TileType::Png)
+                    .internal_compression(internal)
+                    .metadata(r#"{"name":"test"}"#)
+                    .zoom_levels(0..=4)
+                    .tile(TileCoord::new(5, 3, 3).unwrap(), "same")
+                    .tile(TileCoord::new(5, 3, 2).unwrap(), "same");
+                if let Some(leaf_size) = leaf_size {
+                    builder = builder.leaf_size(leaf_size);
+                }
+                let archive = builder.build().await.unwrap();
+
+                let backend = MemoryBackend::new(archive);
+                let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+                let header = tiles.get_header();
+                assert_eq!((header.min_zoom, header.max_zoom), (0, 5));
+                assert_eq!(header.n_addressed_tiles().unwrap().get(), 343);
+                assert_eq!(header.n_tile_entries().unwrap().get(), 342);
+                assert_eq!(tiles.get_metadata().await.unwrap(), r#"{"name":"test"}"#);
+
+                assert_eq!(tiles.get_tile(0, 0, 0).await.unwrap().unwrap(), "0/0/0");
+                assert_eq!(tiles.get_tile(4, 9, 13).await.unwrap().unwrap(), "4/9/13");
+                assert_eq!(tiles.get_tile(5, 3, 2).await.unwrap().unwrap(), "same");
+                assert_eq!(tiles.get_tile(5, 3, 3).await.unwrap().unwrap(), "same");
+                assert!(tiles.get_tile(5, 0, 0).await.unwrap().is_none());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn compressed_tiles() {
+        let archive = ArchiveBuilder::new(TileType::Mvt)
+            .tile_compression(Compression::Gzip)
+            .zoom_levels(0..=1)
+            .build()
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(MemoryBackend::new(archive))
+            .await
+            .unwrap();
+        assert_eq!(tiles.get_header().tile_compression, Compression::Gzip);
+
+        let tile = tiles.get_tile(1, 1, 0).await.unwrap().unwrap();
+        let tile = crate::codec::decompress(Compression::Gzip, tile)
+            .await
+            .unwrap();
+        assert_eq!(tile, "1/1/0");
+    }
+
+    #[tokio::test]
+    async fn truncated_archive() {
+        let archive = ArchiveBuilder::new(TileType::Png)
+            .zoom_levels(0..=2)
+            .build()
+            .await
+            .unwrap();
+        let size = archive.len() as u64;
+        let truncated = MemoryBackend::new(archive.slice(..archive.len() - 1));
+        let result = AsyncPmTilesReader::try_from_source(truncated).await;
+        assert!(
+            matches!(result, Err(PmtError::ArchiveTruncated(expected, actual)) if expected == size && actual == size - 1)
+        );
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mmap-async-tokio")]
+mod mmap_tests {
+    use super::{FlakyBackend, RecordedRead, RecordingBackend};
+    use crate::async_reader::AsyncBackend;
+    use crate::tests::RASTER_FILE;
+    use crate::{MmapBackend, PmtError};
+
+    #[tokio::test]
+    async fn flaky_backend() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let backend = FlakyBackend::new(backend).fail_every(3).truncate_every(2);
+
+        assert_eq!(backend.read(0, 10).await.unwrap().len(), 10);
+        assert_eq!(backend.read(0, 10).await.unwrap().len(), 5);
+        assert!(matches!(
+            backend.read(0, 10).await,
+            Err(PmtError::Reading(_))
+        ));
+        assert!(matches!(
+            backend.read_exact(0, 10).await,
+            Err(PmtError::UnexpectedNumberOfBytesReturned(10, 5))
+        ));
+
+        backend.fail_next(1);
+        assert!(backend.read(0, 10).await.is_err());
+        assert!(backend.read(0, 10).await.is_err());
+        assert_eq!(backend.read(0, 10).await.unwrap().len(), 10);
+        assert_eq!(backend.reads(), 7);
+    }
+
+    #[tokio::test]
+    async fn recording_backend() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let backend = RecordingBackend::new(backend);
+
+        backend.read(0, 10).await.unwrap();
+        backend.read_exact(usize::MAX / 2, 10).await.unwrap_err();
+        assert_eq!(
+            backend.reads(),
+            [
+                RecordedRead {
+                    offset: 0,
+                    length: 10,
+                    exact: false,
+                    received: Some(10),
+                },
+                RecordedRead {
+                    offset: usize::MAX / 2,
+                    length: 10,
+                    exact: true,
+                    received: None,
+                },
+            ]
+        );
+
+        backend.clear();
+        assert!(backend.reads().is_empty());
+    }
+}
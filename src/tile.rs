@@ -1,5 +1,9 @@
 #![allow(clippy::unreadable_literal)]
 
+use std::ops::Range;
+
+use crate::error::{PmtError, PmtResult};
+
 const PYRAMID_SIZE_BY_ZOOM: [u64; 21] = [
     /*  0 */ 0,
     /*  1 */ 1,
@@ -24,28 +28,307 @@ const PYRAMID_SIZE_BY_ZOOM: [u64; 21] = [
     /* 20 */ 366503875925,
 ];
 
-pub(crate) fn tile_id(z: u8, x: u64, y: u64) -> u64 {
-    // The 0/0/0 case is not needed for the base id computation, but it will fail hilbert_2d::u64::xy2h_discrete
-    if z == 0 {
-        return 0;
-    }
-
+/// The first tile ID at zoom level `z`, i.e. the number of tiles in all lower zoom levels.
+fn base_id(z: u8) -> u64 {
     let z_ind = usize::from(z);
-    let base_id = if z_ind < PYRAMID_SIZE_BY_ZOOM.len() {
+    if z_ind < PYRAMID_SIZE_BY_ZOOM.len() {
         PYRAMID_SIZE_BY_ZOOM[z_ind]
     } else {
         let last_ind = PYRAMID_SIZE_BY_ZOOM.len() - 1;
         PYRAMID_SIZE_BY_ZOOM[last_ind] + (last_ind..z_ind).map(|i| 1_u64 << (i << 1)).sum::<u64>()
-    };
+    }
+}
+
+pub(crate) fn tile_id(z: u8, x: u64, y: u64) -> u64 {
+    // The 0/0/0 case is not needed for the base id computation, but it will fail hilbert_2d::u64::xy2h_discrete
+    if z == 0 {
+        return 0;
+    }
 
     let tile_id = hilbert_2d::u64::xy2h_discrete(x, y, z.into(), hilbert_2d::Variant::Hilbert);
 
-    base_id + tile_id
+    base_id(z) + tile_id
+}
+
+/// Maximum zoom level whose tile IDs fit into a `u64`.
+pub const MAX_ZOOM: u8 = 31;
+
+/// Web-mercator latitude limit, in degrees.
+pub(crate) const MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+
+/// A tile address in the XYZ (slippy map) scheme, with `y` growing southward.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileCoord {
+    z: u8,
+    x: u64,
+    y: u64,
+}
+
+impl TileCoord {
+    /// Create a new tile coordinate, validating that `x` and `y` exist at zoom `z`.
+    pub fn new(z: u8, x: u64, y: u64) -> PmtResult<Self> {
+        if z > MAX_ZOOM || x >> z != 0 || y >> z != 0 {
+            return Err(PmtError::InvalidTileCoord);
+        }
+        Ok(Self { z, x, y })
+    }
+
+    /// Find the tile containing a longitude/latitude point at the given zoom.
+    ///
+    /// Points outside the web-mercator extent are clamped to the nearest edge tile.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn from_lon_lat(zoom: u8, longitude: f64, latitude: f64) -> PmtResult<Self> {
+        if zoom > MAX_ZOOM {
+            return Err(PmtError::InvalidTileCoord);
+        }
+        let n = (1_u64 << zoom) as f64;
+        let max_index = (1_u64 << zoom) - 1;
+
+        let longitude = longitude.clamp(-180.0, 180.0);
+        let latitude = latitude.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+
+        let x = ((longitude + 180.0) / 360.0 * n).floor();
+        let y = ((1.0 - latitude.tan().asinh() / std::f64::consts::PI) / 2.0 * n).floor();
+
+        Ok(Self {
+            z: zoom,
+            x: (x.max(0.0) as u64).min(max_index),
+            y: (y.max(0.0) as u64).min(max_index),
+        })
+    }
+
+    /// Create a tile coordinate from the TMS scheme, where `y` grows northward as in `MBTiles`.
+    pub fn from_tms(z: u8, x: u64, y: u64) -> PmtResult<Self> {
+        if z > MAX_ZOOM || y >> z != 0 {
+            return Err(PmtError::InvalidTileCoord);
+        }
+        Self::new(z, x, (1 << z) - 1 - y)
+    }
+
+    /// Parse a Bing-style quadkey. The empty quadkey is the single zoom 0 tile.
+    pub fn from_quadkey(quadkey: &str) -> PmtResult<Self> {
+        let z = u8::try_from(quadkey.len()).map_err(|_| PmtError::InvalidTileCoord)?;
+        let (mut x, mut y) = (0, 0);
+        for digit in quadkey.bytes() {
+            let digit = match digit {
+                b'0'..=b'3' => u64::from(digit - b'0'),
+                _ => return Err(PmtError::InvalidTileCoord),
+            };
+            x = (x << 1) | (digit & 1);
+            y = (y << 1) | (digit >> 1);
+        }
+        Self::new(z, x, y)
+    }
+
+    #[must_use]
+    pub fn z(&self) -> u8 {
+        self.z
+    }
+
+    #[must_use]
+    pub fn x(&self) -> u64 {
+        self.x
+    }
+
+    #[must_use]
+    pub fn y(&self) -> u64 {
+        self.y
+    }
+
+    /// The `y` coordinate in the TMS scheme, where `y` grows northward as in `MBTiles`.
+    #[must_use]
+    pub fn tms_y(&self) -> u64 {
+        (1 << self.z) - 1 - self.y
+    }
+
+    /// Encode the tile as a Bing-style quadkey.
+    #[must_use]
+    pub fn to_quadkey(&self) -> String {
+        (1..=self.z)
+            .rev()
+            .map(|level| {
+                let mask = 1 << (level - 1);
+                let digit = u8::from(self.x & mask != 0) + 2 * u8::from(self.y & mask != 0);
+                char::from(b'0' + digit)
+            })
+            .collect()
+    }
+
+    /// Longitude/latitude extent of the tile as `(min_lon, min_lat, max_lon, max_lat)`.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        let n = (1_u64 << self.z) as f64;
+        let lon = |x: u64| x as f64 / n * 360.0 - 180.0;
+        let lat = |y: u64| {
+            (std::f64::consts::PI * (1.0 - 2.0 * y as f64 / n))
+                .sinh()
+                .atan()
+                .to_degrees()
+        };
+        (lon(self.x), lat(self.y + 1), lon(self.x + 1), lat(self.y))
+    }
+}
+
+/// Largest valid tile ID, the last tile at [`MAX_ZOOM`].
+const MAX_TILE_ID: u64 = u64::MAX / 3 - 1;
+
+/// A tile ID as used in `PMTiles` directories: tiles are numbered zoom level by zoom level,
+/// and along a Hilbert curve within each zoom level.
+///
+/// Because the Hilbert curve is self-similar, all descendants of a tile at a given zoom level
+/// form a contiguous range of IDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TileId(u64);
+
+impl TileId {
+    pub fn new(id: u64) -> PmtResult<Self> {
+        if id > MAX_TILE_ID {
+            return Err(PmtError::InvalidTileCoord);
+        }
+        Ok(Self(id))
+    }
+
+    #[must_use]
+    pub fn value(self) -> u64 {
+        self.0
+    }
+
+    /// Zoom level of this tile.
+    #[must_use]
+    pub fn zoom(self) -> u8 {
+        (1..=MAX_ZOOM)
+            .take_while(|&z| base_id(z) <= self.0)
+            .last()
+            .unwrap_or(0)
+    }
+
+    /// Position of this tile along the Hilbert curve of its zoom level.
+    fn hilbert_index(self) -> u64 {
+        self.0 - base_id(self.zoom())
+    }
+
+    /// The tile one zoom level up containing this tile, or `None` at zoom 0.
+    #[must_use]
+    pub fn parent(self) -> Option<Self> {
+        self.ancestor_at(self.zoom().checked_sub(1)?)
+    }
+
+    /// The four tiles one zoom level down, in tile ID order, or `None` at [`MAX_ZOOM`].
+    #[must_use]
+    pub fn children(self) -> Option<[Self; 4]> {
+        let zoom = self.zoom();
+        if zoom >= MAX_ZOOM {
+            return None;
+        }
+        let first = base_id(zoom + 1) + (self.hilbert_index() << 2);
+        Some([
+            Self(first),
+            Self(first + 1),
+            Self(first + 2),
+            Self(first + 3),
+        ])
+    }
+
+    /// The tile at `zoom` containing this tile, or `None` if `zoom` is deeper than this tile.
+    /// Returns the tile itself if `zoom` is its own zoom level.
+    #[must_use]
+    pub fn ancestor_at(self, zoom: u8) -> Option<Self> {
+        let own_zoom = self.zoom();
+        let levels = own_zoom.checked_sub(zoom)?;
+        Some(Self(base_id(zoom) + (self.hilbert_index() >> (2 * levels))))
+    }
+
+    /// The contiguous range of raw tile IDs at `zoom` that lie within this tile,
+    /// or `None` if `zoom` is above this tile or beyond [`MAX_ZOOM`].
+    #[must_use]
+    pub fn descendants_range(self, zoom: u8) -> Option<Range<u64>> {
+        if zoom > MAX_ZOOM {
+            return None;
+        }
+        let levels = zoom.checked_sub(self.zoom())?;
+        let start = base_id(zoom) + (self.hilbert_index() << (2 * levels));
+        Some(start..start + (1 << (2 * levels)))
+    }
+
+    /// Iterate over all tiles of this tile's subtree from `min_zoom` to `max_zoom` inclusive,
+    /// in tile ID order. Zoom levels above this tile or beyond [`MAX_ZOOM`] are skipped.
+    pub fn subtree(self, min_zoom: u8, max_zoom: u8) -> impl Iterator<Item = TileId> {
+        (min_zoom..=max_zoom)
+            .filter_map(move |zoom| self.descendants_range(zoom))
+            .flatten()
+            .map(TileId)
+    }
+}
+
+/// The smallest `(min_x, min_y, max_x, max_y)` extent containing the tiles with IDs in `ids`,
+/// which must all be at `zoom`, or `None` if `ids` is empty.
+///
+/// Instead of decoding every tile, the range is split into aligned blocks that each fill the
+/// subtree of a single tile at a lower zoom level, so only a few tiles per zoom level are decoded.
+#[cfg(any(test, feature = "__async"))]
+pub(crate) fn tile_extent(zoom: u8, ids: Range<u64>) -> Option<(u64, u64, u64, u64)> {
+    let base = base_id(zoom);
+    let (mut pos, end) = (ids.start.checked_sub(base)?, ids.end.checked_sub(base)?);
+    let mut extent: Option<(u64, u64, u64, u64)> = None;
+    while pos < end {
+        let fits = |levels: u8| {
+            let size = 1_u64 << (2 * levels);
+            pos % size == 0 && pos + size <= end
+        };
+        let mut levels = 0;
+        while levels < zoom && fits(levels + 1) {
+            levels += 1;
+        }
+
+        let block = TileCoord::from(TileId(base_id(zoom - levels) + (pos >> (2 * levels))));
+        let (min_x, min_y) = (block.x << levels, block.y << levels);
+        let (max_x, max_y) = (min_x + (1 << levels) - 1, min_y + (1 << levels) - 1);
+        extent = Some(match extent {
+            Some((x0, y0, x1, y1)) => (x0.min(min_x), y0.min(min_y), x1.max(max_x), y1.max(max_y)),
+            None => (min_x, min_y, max_x, max_y),
+        });
+        pos += 1 << (2 * levels);
+    }
+    extent
+}
+
+impl From<TileId> for u64 {
+    fn from(id: TileId) -> Self {
+        id.0
+    }
+}
+
+impl From<TileCoord> for TileId {
+    fn from(coord: TileCoord) -> Self {
+        Self(tile_id(coord.z, coord.x, coord.y))
+    }
+}
+
+impl From<TileId> for TileCoord {
+    fn from(id: TileId) -> Self {
+        let z = id.zoom();
+        let (x, y) = if z == 0 {
+            (0, 0)
+        } else {
+            hilbert_2d::u64::h2xy_discrete(
+                id.hilbert_index(),
+                z.into(),
+                hilbert_2d::Variant::Hilbert,
+            )
+        };
+        Self { z, x, y }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::tile_id;
+    #![allow(clippy::float_cmp)]
+    use super::{tile_extent, tile_id, TileCoord, TileId, MAX_LATITUDE, MAX_TILE_ID, MAX_ZOOM};
 
     #[test]
     fn test_tile_id() {
@@ -64,4 +347,179 @@ mod test {
         assert_eq!(tile_id(27, 0, 0), 6004799503160661);
         assert_eq!(tile_id(28, 0, 0), 24019198012642645);
     }
+
+    #[test]
+    fn test_tile_coord_new() {
+        assert!(TileCoord::new(0, 0, 0).is_ok());
+        assert!(TileCoord::new(3, 7, 7).is_ok());
+        assert!(TileCoord::new(3, 8, 0).is_err());
+        assert!(TileCoord::new(3, 0, 8).is_err());
+        assert!(TileCoord::new(31, (1 << 31) - 1, 0).is_ok());
+        assert!(TileCoord::new(32, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_from_lon_lat() {
+        let coord = TileCoord::from_lon_lat(12, 11.2558, 43.7696).unwrap();
+        assert_eq!((coord.z(), coord.x(), coord.y()), (12, 2176, 1493));
+
+        assert_eq!(
+            TileCoord::from_lon_lat(0, 11.2558, 43.7696).unwrap(),
+            TileCoord::new(0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            TileCoord::from_lon_lat(2, 180.0, -90.0).unwrap(),
+            TileCoord::new(2, 3, 3).unwrap()
+        );
+        assert_eq!(
+            TileCoord::from_lon_lat(2, -180.0, 90.0).unwrap(),
+            TileCoord::new(2, 0, 0).unwrap()
+        );
+        assert!(TileCoord::from_lon_lat(32, 0.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_tile_bounds() {
+        let world = TileCoord::new(0, 0, 0).unwrap().bounds();
+        assert!((world.1 + MAX_LATITUDE).abs() < 1e-9 && (world.3 - MAX_LATITUDE).abs() < 1e-9);
+        assert_eq!((world.0, world.2), (-180.0, 180.0));
+
+        let (min_lon, min_lat, max_lon, max_lat) = TileCoord::new(1, 1, 0).unwrap().bounds();
+        assert_eq!((min_lon, max_lon), (0.0, 180.0));
+        assert!(min_lat.abs() < 1e-9);
+        assert!((max_lat - MAX_LATITUDE).abs() < 1e-9);
+
+        let coord = TileCoord::from_lon_lat(12, 11.2558, 43.7696).unwrap();
+        let (min_lon, min_lat, max_lon, max_lat) = coord.bounds();
+        assert!(min_lon <= 11.2558 && 11.2558 < max_lon);
+        assert!(min_lat < 43.7696 && 43.7696 <= max_lat);
+    }
+
+    #[test]
+    fn test_subtree() {
+        let root = TileId::new(0).unwrap();
+        let ids: Vec<u64> = root.subtree(0, 2).map(TileId::value).collect();
+        assert_eq!(ids, (0..21).collect::<Vec<_>>());
+
+        let tile = TileId::from(TileCoord::new(2, 1, 3).unwrap());
+        assert_eq!(tile.subtree(0, 1).count(), 0);
+        assert_eq!(tile.subtree(2, 2).collect::<Vec<_>>(), vec![tile]);
+
+        let subtree: Vec<TileId> = tile.subtree(3, 5).collect();
+        assert_eq!(subtree.len(), 4 + 16 + 64);
+        assert!(subtree.windows(2).all(|w| w[0] < w[1]));
+        assert!(subtree.iter().all(|t| t.ancestor_at(2) == Some(tile)));
+
+        let last = TileId::new(MAX_TILE_ID).unwrap();
+        assert_eq!(last.subtree(MAX_ZOOM, u8::MAX).count(), 1);
+    }
+
+    #[test]
+    fn test_tms() {
+        let coord = TileCoord::new(3, 2, 1).unwrap();
+        assert_eq!(coord.tms_y(), 6);
+        assert_eq!(TileCoord::from_tms(3, 2, 6).unwrap(), coord);
+        assert_eq!(TileCoord::new(0, 0, 0).unwrap().tms_y(), 0);
+        assert!(TileCoord::from_tms(3, 2, 8).is_err());
+    }
+
+    #[test]
+    fn test_quadkey() {
+        let coord = TileCoord::new(3, 3, 5).unwrap();
+        assert_eq!(coord.to_quadkey(), "213");
+        assert_eq!(TileCoord::from_quadkey("213").unwrap(), coord);
+
+        assert_eq!(TileCoord::new(0, 0, 0).unwrap().to_quadkey(), "");
+        assert_eq!(
+            TileCoord::from_quadkey("").unwrap(),
+            TileCoord::new(0, 0, 0).unwrap()
+        );
+
+        let coord = TileCoord::new(12, 2174, 1492).unwrap();
+        assert_eq!(TileCoord::from_quadkey(&coord.to_quadkey()).unwrap(), coord);
+
+        assert!(TileCoord::from_quadkey("124").is_err());
+        assert!(TileCoord::from_quadkey(&"0".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn test_tile_id_zoom() {
+        assert_eq!(TileId::new(0).unwrap().zoom(), 0);
+        assert_eq!(TileId::new(1).unwrap().zoom(), 1);
+        assert_eq!(TileId::new(4).unwrap().zoom(), 1);
+        assert_eq!(TileId::new(5).unwrap().zoom(), 2);
+        assert_eq!(TileId::new(366503875925).unwrap().zoom(), 20);
+        assert_eq!(TileId::new(MAX_TILE_ID).unwrap().zoom(), MAX_ZOOM);
+        assert!(TileId::new(MAX_TILE_ID + 1).is_err());
+    }
+
+    #[test]
+    fn test_tile_id_coord_round_trip() {
+        for (z, x, y) in [(0, 0, 0), (1, 1, 0), (2, 1, 3), (3, 3, 0), (12, 2174, 1492)] {
+            let coord = TileCoord::new(z, x, y).unwrap();
+            let id = TileId::from(coord);
+            assert_eq!(id.value(), tile_id(z, x, y));
+            assert_eq!(TileCoord::from(id), coord);
+        }
+    }
+
+    #[test]
+    fn test_tile_id_hierarchy() {
+        let root = TileId::new(0).unwrap();
+        assert_eq!(root.parent(), None);
+        assert_eq!(root.ancestor_at(1), None);
+        assert_eq!(root.children().unwrap().map(TileId::value), [1, 2, 3, 4]);
+        assert_eq!(root.descendants_range(2), Some(5..21));
+
+        let coord = TileCoord::new(12, 2174, 1492).unwrap();
+        let id = TileId::from(coord);
+        let parent = TileCoord::from(id.parent().unwrap());
+        assert_eq!((parent.z(), parent.x(), parent.y()), (11, 1087, 746));
+
+        let ancestor = TileCoord::from(id.ancestor_at(8).unwrap());
+        assert_eq!((ancestor.z(), ancestor.x(), ancestor.y()), (8, 135, 93));
+        assert_eq!(id.ancestor_at(12), Some(id));
+
+        for child in id.children().unwrap() {
+            assert_eq!(child.parent(), Some(id));
+            let child = TileCoord::from(child);
+            assert_eq!((child.x() >> 1, child.y() >> 1), (2174, 1492));
+        }
+
+        let range = id.ancestor_at(10).unwrap().descendants_range(12).unwrap();
+        assert_eq!(range.end - range.start, 16);
+        assert!(range.contains(&id.value()));
+        for raw in range {
+            let tile = TileId::new(raw).unwrap();
+            assert_eq!(tile.ancestor_at(10), id.ancestor_at(10));
+        }
+
+        let last = TileId::new(MAX_TILE_ID).unwrap();
+        assert_eq!(last.children(), None);
+        assert_eq!(last.descendants_range(MAX_ZOOM + 1), None);
+    }
+
+    #[test]
+    fn test_tile_extent() {
+        let zoom_ids = TileId::new(0).unwrap().descendants_range(3).unwrap();
+        assert_eq!(zoom_ids, 21..85);
+        for start in zoom_ids.clone() {
+            for end in start..=zoom_ids.end {
+                let expected = (start..end).map(|id| TileCoord::from(TileId(id))).fold(
+                    None,
+                    |extent: Option<(u64, u64, u64, u64)>, c| {
+                        Some(extent.map_or((c.x, c.y, c.x, c.y), |(x0, y0, x1, y1)| {
+                            (x0.min(c.x), y0.min(c.y), x1.max(c.x), y1.max(c.y))
+                        }))
+                    },
+                );
+                assert_eq!(tile_extent(3, start..end), expected, "{start}..{end}");
+            }
+        }
+
+        // The whole zoom level is a single block
+        let z20 = TileId::new(0).unwrap().descendants_range(20).unwrap();
+        let max = (1 << 20) - 1;
+        assert_eq!(tile_extent(20, z20), Some((0, 0, max, max)));
+    }
 }
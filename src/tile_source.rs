@@ -0,0 +1,92 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use bytes::Bytes;
+
+use crate::async_reader::{AsyncBackend, AsyncPmTilesReader};
+use crate::cache::DirectoryCache;
+use crate::error::PmtResult;
+
+/// A boxed future returned by [`TileSource`] methods.
+pub type TileSourceFuture<'a, T> = Pin<Box<dyn Future<Output = PmtResult<T>> + Send + 'a>>;
+
+/// A source of map tiles, as needed to serve them over HTTP.
+///
+/// This is the integration point for tile servers: they can serve any `TileSource` without
+/// depending on the reader's backend and cache types, and use it to wrap other tile formats.
+/// The trait is object safe, so servers can keep sources of different types together as
+/// `Box<dyn TileSource>`, at the cost of one allocation per call, as with
+/// [`BoxedBackend`](crate::BoxedBackend).
+pub trait TileSource: Send + Sync {
+    /// Fetches the tile's bytes as stored, i.e. encoded with [`Self::content_encoding`].
+    fn get_tile(&self, z: u8, x: u64, y: u64) -> TileSourceFuture<'_, Option<Bytes>>;
+
+    /// Describes the tiles as `TileJSON`, with `sources` as the tile URLs.
+    fn tilejson(&self, sources: Vec<String>) -> TileSourceFuture<'_, tilejson::TileJSON>;
+
+    /// The `Content-Type` to serve tiles with.
+    fn content_type(&self) -> &'static str;
+
+    /// The `Content-Encoding` to serve tiles with, if they are compressed.
+    fn content_encoding(&self) -> Option<&'static str>;
+}
+
+impl<B: AsyncBackend + Sync + Send, C: DirectoryCache + Sync + Send> TileSource
+    for AsyncPmTilesReader<B, C>
+{
+    fn get_tile(&self, z: u8, x: u64, y: u64) -> TileSourceFuture<'_, Option<Bytes>> {
+        Box::pin(AsyncPmTilesReader::get_tile(self, z, x, y))
+    }
+
+    fn tilejson(&self, sources: Vec<String>) -> TileSourceFuture<'_, tilejson::TileJSON> {
+        Box::pin(self.parse_tilejson(sources))
+    }
+
+    fn content_type(&self) -> &'static str {
+        self.get_header().tile_type.content_type()
+    }
+
+    fn content_encoding(&self) -> Option<&'static str> {
+        self.get_header().tile_compression.content_encoding()
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mmap-async-tokio")]
+mod tests {
+    use super::TileSource;
+    use crate::async_reader::AsyncPmTilesReader;
+    use crate::tests::{RASTER_FILE, VECTOR_FILE};
+    use crate::MmapBackend;
+
+    /// Serves a tile the way a server holding sources of different types would.
+    async fn serve(source: &dyn TileSource) -> (usize, &'static str, Option<&'static str>) {
+        let tile = source.get_tile(0, 0, 0).await.unwrap().unwrap();
+        (tile.len(), source.content_type(), source.content_encoding())
+    }
+
+    #[tokio::test]
+    async fn reader_as_tile_source() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let raster = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let vector = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let sources: Vec<Box<dyn TileSource>> = vec![Box::new(raster), Box::new(vector)];
+
+        let fixture_tile = include_bytes!("../fixtures/0_0_0.png");
+        assert_eq!(
+            serve(&*sources[0]).await,
+            (fixture_tile.len(), "image/png", None)
+        );
+        let (_, content_type, content_encoding) = serve(&*sources[1]).await;
+        assert_eq!(content_type, "application/vnd.mapbox-vector-tile");
+        assert_eq!(content_encoding, Some("gzip"));
+
+        let tj = sources[1]
+            .tilejson(vec!["https://example.com/{z}/{x}/{y}.mvt".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(tj.tiles.len(), 1);
+        assert!(tj.attribution.is_some());
+    }
+}